@@ -0,0 +1,247 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use enum_iterator::all;
+use serde::Deserialize;
+
+use crate::{constants::ANIMATION_DURATION, events::AnimationEvent, frames::TexturePack, GameAssetType, GameAssets};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayMode {
+    #[default]
+    Loop,
+    /// Stops on the last frame
+    Once,
+    /// Goes back and forth
+    PingPong,
+}
+
+/// How a clip is played
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ClipDefinition {
+    /// Seconds each frame is shown, the last one also for the frames not listed
+    pub frame_durations: Vec<f32>,
+    pub mode: PlayMode,
+    /// Sent when the given frames are shown, e.g. `hit` when an attack lands
+    pub events: HashMap<usize, String>,
+}
+
+/// How the clips are played, by the start of their names (e.g. `player/attack` for all the directions)
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[uuid = "10421785-e112-48d1-94bc-fbf3746c7eca"]
+pub struct ClipDefinitions(HashMap<String, ClipDefinition>);
+
+impl ClipDefinitions {
+    /// The definition with the longest name matching the clip
+    fn get(&self, clip: &str) -> Option<&ClipDefinition> {
+        self.0
+            .iter()
+            .filter(|(name, _)| clip.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map(|(_, definition)| definition)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    /// Indices of the frames in the atlas
+    pub frames: Vec<usize>,
+    durations: Vec<Duration>,
+    mode: PlayMode,
+    events: HashMap<usize, String>,
+}
+
+impl AnimationClip {
+    pub fn new(frames: Vec<usize>, definition: Option<&ClipDefinition>) -> Self {
+        let definition = definition.cloned().unwrap_or_default();
+        let last = definition
+            .frame_durations
+            .last()
+            .map_or(ANIMATION_DURATION, |duration| Duration::from_secs_f32(*duration));
+
+        let durations = (0..frames.len())
+            .map(|frame| {
+                definition
+                    .frame_durations
+                    .get(frame)
+                    .map_or(last, |duration| Duration::from_secs_f32(*duration))
+            })
+            .collect();
+
+        Self {
+            frames,
+            durations,
+            mode: definition.mode,
+            events: definition.events,
+        }
+    }
+
+    pub fn event(&self, frame: usize) -> Option<&String> {
+        self.events.get(&frame)
+    }
+}
+
+/// The clips of all the texture packs, by name
+#[derive(Default, Resource)]
+pub struct AnimationClips(HashMap<String, AnimationClip>);
+
+impl AnimationClips {
+    pub fn new(assets: &GameAssets, textures: &Assets<TexturePack>, definitions: &ClipDefinitions) -> Self {
+        let clips = all::<GameAssetType>()
+            .flat_map(|ty| assets.pack(ty, textures).clips())
+            .map(|(name, frames)| (name.clone(), AnimationClip::new(frames.clone(), definitions.get(name))))
+            .collect();
+
+        Self(clips)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AnimationClip> {
+        self.0.get(name)
+    }
+}
+
+/// Plays the clip of an entity, restarting whenever the clip changes
+#[derive(Component, Default)]
+pub struct Animation {
+    clip: Option<String>,
+    current_frame: usize,
+    /// Going back to the first frame, when ping-ponging
+    backwards: bool,
+    timer: Timer,
+}
+
+impl Animation {
+    fn play(&mut self, name: String, clip: &AnimationClip) {
+        self.clip = Some(name);
+        self.current_frame = 0;
+        self.backwards = false;
+        self.timer = Timer::new(clip.durations[0], TimerMode::Repeating);
+    }
+
+    /// Restarts the clip, even if it doesn't change
+    pub fn stop(&mut self) {
+        self.clip = None;
+    }
+
+    /// The new frame of the clip, if it changed
+    fn next_frame(&mut self, clip: &AnimationClip, delta: Duration) -> Option<usize> {
+        self.timer.tick(delta);
+
+        if !self.timer.just_finished() {
+            return None;
+        }
+
+        let last = clip.frames.len() - 1;
+        let frame = match clip.mode {
+            _ if last == 0 => return None,
+            PlayMode::Loop => (self.current_frame + 1) % clip.frames.len(),
+            PlayMode::Once if self.current_frame == last => return None,
+            PlayMode::Once => self.current_frame + 1,
+            PlayMode::PingPong => {
+                if self.current_frame == last {
+                    self.backwards = true;
+                } else if self.current_frame == 0 {
+                    self.backwards = false;
+                }
+
+                if self.backwards {
+                    self.current_frame - 1
+                } else {
+                    self.current_frame + 1
+                }
+            },
+        };
+
+        self.current_frame = frame;
+        self.timer.set_duration(clip.durations[frame]);
+
+        Some(frame)
+    }
+}
+
+pub trait AnimatedEntity {
+    /// The name of the clip to play, e.g. `player/move/down`
+    fn clip_name(&self) -> String;
+}
+
+pub fn render_animation(
+    entity: Entity,
+    game_entity: &dyn AnimatedEntity,
+    animation: &mut Animation,
+    sprite: &mut TextureAtlasSprite,
+    time: &Res<Time>,
+    clips: &AnimationClips,
+    animation_event_writer: &mut EventWriter<AnimationEvent>,
+) {
+    let name = game_entity.clip_name();
+    let Some(clip) = clips.get(&name) else {
+        return;
+    };
+
+    let frame = if animation.clip.as_ref() != Some(&name) {
+        animation.play(name, clip);
+        Some(0)
+    } else {
+        animation.next_frame(clip, time.delta())
+    };
+
+    if let Some(frame) = frame {
+        sprite.index = clip.frames[frame];
+
+        if let Some(event) = clip.event(frame) {
+            animation_event_writer.send(AnimationEvent {
+                entity,
+                name: event.clone(),
+            });
+        }
+    }
+}
+
+/// Rebuilds the clips when a texture pack changes on disk
+pub fn reload_animation_clips(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    textures: Res<Assets<TexturePack>>,
+    definitions: Res<ClipDefinitions>,
+    mut texture_events: EventReader<AssetEvent<TexturePack>>,
+) {
+    let modified = texture_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+
+    if modified {
+        commands.insert_resource(AnimationClips::new(&assets, &textures, &definitions));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn play_modes() {
+        let definition = |mode| ClipDefinition {
+            frame_durations: vec![0.25],
+            mode,
+            ..default()
+        };
+        let frames = |mode| {
+            let clip = AnimationClip::new(vec![10, 11, 12], Some(&definition(mode)));
+            let mut animation = Animation::default();
+            animation.play("clip".to_string(), &clip);
+
+            (0..5)
+                .map(|_| {
+                    animation.next_frame(&clip, Duration::from_millis(250));
+                    animation.current_frame
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(frames(PlayMode::Loop), vec![1, 2, 0, 1, 2]);
+        assert_eq!(frames(PlayMode::Once), vec![1, 2, 2, 2, 2]);
+        assert_eq!(frames(PlayMode::PingPong), vec![1, 2, 1, 0, 1]);
+    }
+}
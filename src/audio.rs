@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use bevy::{ecs::schedule::common_conditions::not, prelude::*};
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioInstance, AudioPlugin, AudioSource, PlaybackState};
+
+use crate::{
+    constants::{MAX_SIMULTANEOUS_SOUNDS, MAX_SOUND_DISTANCE, VOLUME_STEP},
+    screens::is_in_console,
+    settings::Settings,
+    settings_file,
+};
+
+#[derive(Resource)]
+pub struct MusicChannel;
+
+#[derive(Resource)]
+pub struct SfxChannel;
+
+#[derive(Resource)]
+pub struct UiChannel;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundChannel {
+    Sfx,
+    Ui,
+}
+
+pub struct PlaySound {
+    pub path: String,
+    pub channel: SoundChannel,
+    pub volume: f64,
+    pub pos: Option<Vec3>,
+}
+
+impl PlaySound {
+    pub fn sfx(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            channel: SoundChannel::Sfx,
+            volume: 1.0,
+            pos: None,
+        }
+    }
+
+    pub fn ui(path: impl Into<String>) -> Self {
+        Self {
+            channel: SoundChannel::Ui,
+            ..Self::sfx(path)
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f64) -> Self {
+        self.volume = volume;
+        self
+    }
+
+    /// Attenuates and pans the sound according to its distance from the camera
+    pub fn at(mut self, pos: Vec3) -> Self {
+        self.pos = Some(pos);
+        self
+    }
+}
+
+/// Instances of the sounds currently playing, by asset path
+#[derive(Default, Resource)]
+pub struct PlayingSounds(HashMap<String, Vec<Handle<AudioInstance>>>);
+
+pub struct GameAudioPlugin;
+
+impl Plugin for GameAudioPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(AudioPlugin)
+            .add_audio_channel::<MusicChannel>()
+            .add_audio_channel::<SfxChannel>()
+            .add_audio_channel::<UiChannel>()
+            .add_event::<PlaySound>()
+            .init_resource::<PlayingSounds>()
+            .add_systems((apply_volumes, adjust_volumes.run_if(not(is_in_console)), play_sounds));
+    }
+}
+
+/// Volume and panning of a sound emitted at `pos`, as heard from `listener`
+pub fn spatialize(pos: Vec3, listener: Vec3) -> (f64, f64) {
+    let diff = (pos - listener).truncate();
+    let attenuation = (1.0 - diff.length() / MAX_SOUND_DISTANCE).clamp(0.0, 1.0);
+    let panning = (0.5 + diff.x / (2.0 * MAX_SOUND_DISTANCE)).clamp(0.0, 1.0);
+
+    (attenuation as f64, panning as f64)
+}
+
+fn play_sounds(
+    asset_server: Res<AssetServer>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    ui: Res<AudioChannel<UiChannel>>,
+    camera_q: Query<&Transform, With<Camera>>,
+    mut playing: ResMut<PlayingSounds>,
+    mut play_sound_reader: EventReader<PlaySound>,
+) {
+    let listener = camera_q.get_single().map(|transform| transform.translation).ok();
+
+    for event in play_sound_reader.iter() {
+        let (attenuation, panning) = match (event.pos, listener) {
+            (Some(pos), Some(listener)) => spatialize(pos, listener),
+            _ => (1.0, 0.5),
+        };
+
+        if attenuation <= 0.0 {
+            continue;
+        }
+
+        let instances = playing.0.entry(event.path.clone()).or_default();
+        let source = asset_server.load(event.path.as_str());
+        let volume = event.volume * attenuation;
+
+        match event.channel {
+            SoundChannel::Sfx => play_on(&sfx, instances, source, volume, panning),
+            SoundChannel::Ui => play_on(&ui, instances, source, volume, panning),
+        }
+    }
+}
+
+fn play_on<T: Resource>(
+    channel: &AudioChannel<T>,
+    instances: &mut Vec<Handle<AudioInstance>>,
+    source: Handle<AudioSource>,
+    volume: f64,
+    panning: f64,
+) {
+    instances.retain(|instance| channel.state(instance) != PlaybackState::Stopped);
+
+    // Too many copies of the same sound are just noise
+    if instances.len() >= MAX_SIMULTANEOUS_SOUNDS {
+        return;
+    }
+
+    let instance = channel.play(source).with_volume(volume).with_panning(panning).handle();
+
+    instances.push(instance);
+}
+
+fn apply_volumes(
+    settings: Res<Settings>,
+    music: Res<AudioChannel<MusicChannel>>,
+    sfx: Res<AudioChannel<SfxChannel>>,
+    ui: Res<AudioChannel<UiChannel>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    let volume = settings.volume;
+
+    music.set_volume(volume.master() * volume.music);
+    sfx.set_volume(volume.master() * volume.sfx);
+    ui.set_volume(volume.master() * volume.ui);
+}
+
+fn adjust_volumes(keyboard_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    let mut volume = settings.volume;
+
+    for key in keyboard_input.get_just_pressed() {
+        match key {
+            KeyCode::Minus => volume.sfx -= VOLUME_STEP,
+            KeyCode::Equals => volume.sfx += VOLUME_STEP,
+            KeyCode::LBracket => volume.music -= VOLUME_STEP,
+            KeyCode::RBracket => volume.music += VOLUME_STEP,
+            _ => continue,
+        }
+    }
+
+    volume.sfx = volume.sfx.clamp(0.0, 1.0);
+    volume.music = volume.music.clamp(0.0, 1.0);
+
+    if volume.sfx != settings.volume.sfx || volume.music != settings.volume.music {
+        settings.volume = volume;
+        Settings::update(&settings_file(), |settings| {
+            settings.volume.sfx = volume.sfx;
+            settings.volume.music = volume.music;
+        });
+    }
+}
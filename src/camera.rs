@@ -1,12 +1,58 @@
-use bevy::prelude::*;
+use bevy::{math::Vec3Swizzles, prelude::*, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
 
-use crate::{constants::CAMERA_SCALE, entities::Player};
+use crate::{
+    constants::{
+        CAMERA_LOOKAHEAD,
+        CAMERA_SMOOTHING,
+        CAMERA_ZOOM,
+        MAX_CAMERA_ZOOM,
+        MAX_SHAKE_OFFSET,
+        MIN_CAMERA_ZOOM,
+        SHAKE_DECAY,
+        ZOOM_STEP,
+    },
+    entities::Player,
+    events::ShakeCamera,
+    rng::{GameRng, RngStream},
+    MapSize,
+};
+
+#[derive(Component, Reflect)]
+pub struct CameraController {
+    /// How fast the camera catches up with its target, 0 to follow it rigidly
+    pub smoothing: f32,
+    /// Distance ahead of the player, in the direction it's moving
+    pub lookahead: f32,
+    pub zoom: f32,
+    pub target_zoom: f32,
+    /// Amount of shake, between 0 and 1, decaying over time
+    pub trauma: f32,
+    /// Followed instead of the player, e.g. while editing the map
+    pub focus: Option<Vec2>,
+    position: Option<Vec2>,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            smoothing: CAMERA_SMOOTHING,
+            lookahead: CAMERA_LOOKAHEAD,
+            zoom: CAMERA_ZOOM,
+            target_zoom: CAMERA_ZOOM,
+            trauma: 0.0,
+            focus: None,
+            position: None,
+        }
+    }
+}
 
 pub fn spawn_camera(mut commands: Commands) {
     // println!("spawn cameras");
     let camera = Camera2dBundle {
         projection: OrthographicProjection {
-            scale: CAMERA_SCALE,
+            scale: CAMERA_ZOOM,
             near: -10000.0,
             far: 10000.0,
             ..default()
@@ -14,14 +60,110 @@ pub fn spawn_camera(mut commands: Commands) {
         ..default()
     };
 
-    commands.spawn(camera);
+    commands.spawn((camera, CameraController::default()));
+}
+
+pub fn zoom_camera(keyboard_input: Res<Input<KeyCode>>, mut camera_q: Query<&mut CameraController>) {
+    let mut controller = camera_q.single_mut();
+
+    for key in keyboard_input.get_just_pressed() {
+        match key {
+            KeyCode::PageUp => controller.target_zoom -= ZOOM_STEP,
+            KeyCode::PageDown => controller.target_zoom += ZOOM_STEP,
+            _ => continue,
+        }
+    }
+
+    controller.target_zoom = controller.target_zoom.clamp(MIN_CAMERA_ZOOM, MAX_CAMERA_ZOOM);
 }
 
+pub fn shake_camera(mut camera_q: Query<&mut CameraController>, mut shake_camera_reader: EventReader<ShakeCamera>) {
+    let mut controller = camera_q.single_mut();
+
+    for event in shake_camera_reader.iter() {
+        controller.trauma = (controller.trauma + event.0).min(1.0);
+    }
+}
+
+type CameraQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut Transform,
+        &'static mut OrthographicProjection,
+        &'static mut CameraController,
+    ),
+    With<Camera>,
+>;
+type TargetQuery<'w, 's> = Query<'w, 's, (&'static Transform, &'static Velocity), (With<Player>, Without<Camera>)>;
+
 pub fn move_camera(
-    mut camera_q: Query<&mut Transform, With<Camera>>,
-    player_q: Query<&Transform, (With<Player>, Without<Camera>)>,
+    time: Res<Time>,
+    size: Res<MapSize>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: CameraQuery,
+    player_q: TargetQuery,
+    mut rng: ResMut<GameRng>,
 ) {
-    let player_transform = player_q.single();
-    let mut camera_transform = camera_q.single_mut();
-    camera_transform.translation = player_transform.translation;
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    let (player_transform, velocity) = player_q.single();
+    let (mut camera_transform, mut projection, mut controller) = camera_q.single_mut();
+    let delta = time.delta_seconds();
+
+    controller.zoom = approach(controller.zoom, controller.target_zoom, controller.smoothing, delta);
+    projection.scale = controller.zoom;
+
+    let target = controller
+        .focus
+        .unwrap_or(player_transform.translation.xy() + velocity.linvel.normalize_or_zero() * controller.lookahead);
+    let position = match controller.position {
+        Some(position) => position.lerp(target, approach(0.0, 1.0, controller.smoothing, delta)),
+        None => target,
+    };
+    controller.position = Some(position);
+
+    let half_view = Vec2::new(window.width(), window.height()) * controller.zoom / 2.0;
+    let mut translation = clamp_to_map(position, half_view, &size, window);
+
+    if controller.trauma > 0.0 {
+        let rng = rng.stream(RngStream::Effects);
+        let strength = controller.trauma * controller.trauma * MAX_SHAKE_OFFSET;
+
+        translation += Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0)) * strength;
+        controller.trauma = (controller.trauma - SHAKE_DECAY * delta).max(0.0);
+    }
+
+    camera_transform.translation = translation.extend(player_transform.translation.z);
+}
+
+/// Moves `value` towards `target`, framerate independently
+fn approach(value: f32, target: f32, smoothing: f32, delta: f32) -> f32 {
+    if smoothing <= 0.0 {
+        return target;
+    }
+
+    value + (target - value) * (1.0 - (-smoothing * delta).exp())
+}
+
+/// Keeps the view inside the map, centering it when the map is smaller than the view
+fn clamp_to_map(position: Vec2, half_view: Vec2, size: &MapSize, window: &Window) -> Vec2 {
+    // The ground is positioned with its top-left corner at the top-left of the window
+    let min = Vec2::new(-window.width() / 2.0, window.height() / 2.0 - size.height);
+    let max = Vec2::new(size.width - window.width() / 2.0, window.height() / 2.0);
+
+    let clamp = |value: f32, min: f32, max: f32, half: f32| {
+        if max - min <= half * 2.0 {
+            (min + max) / 2.0
+        } else {
+            value.clamp(min + half, max - half)
+        }
+    };
+
+    Vec2::new(
+        clamp(position.x, min.x, max.x, half_view.x),
+        clamp(position.y, min.y, max.y, half_view.y),
+    )
 }
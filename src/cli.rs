@@ -0,0 +1,147 @@
+use clap::Parser;
+
+use crate::{
+    map::{LayerType, LevelSelection},
+    replay::{Recording, ReplayMode},
+    settings::Settings,
+};
+
+/// Command line options, overriding the settings file for this run only
+#[derive(Debug, Parser)]
+#[command(about)]
+pub struct Cli {
+    /// Directory of the map to play, in the assets folder
+    #[arg(long)]
+    pub map: Option<String>,
+
+    /// Starts at one of the levels of the map (e.g. `forest` or `lair`)
+    #[arg(long)]
+    pub level: Option<String>,
+
+    /// Seeds every random choice of the game, e.g. to share a speedrun or reproduce a bug
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Size of the window, e.g. `1920x1080`
+    #[arg(long, value_parser = parse_window_size)]
+    pub window_size: Option<(u32, u32)>,
+
+    #[arg(long)]
+    pub fullscreen: bool,
+
+    #[arg(long)]
+    pub mute: bool,
+
+    /// Shows the world inspector
+    #[arg(long)]
+    pub debug_world: bool,
+
+    /// Shows the colliders
+    #[arg(long)]
+    pub debug_physics: bool,
+
+    #[arg(long)]
+    pub max_enemies: Option<usize>,
+
+    #[arg(long)]
+    pub max_tiles: Option<usize>,
+
+    /// The map layers to load, e.g. `blocks,entities`
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub layers: Option<Vec<LayerType>>,
+
+    /// Records the inputs to the given file, on exit
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<String>,
+
+    /// Replays the inputs recorded in the given file
+    #[arg(long)]
+    pub replay: Option<String>,
+
+    /// Packs the images in `assets/images` into the texture atlases, before starting
+    #[arg(long)]
+    pub pack_textures: bool,
+
+    /// Stores the options above in the settings file, for the next runs too
+    #[arg(long)]
+    pub save_settings: bool,
+}
+
+impl Cli {
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some((width, height)) = self.window_size {
+            settings.window.width = width;
+            settings.window.height = height;
+        }
+
+        settings.window.fullscreen |= self.fullscreen;
+        settings.volume.muted |= self.mute;
+        settings.debug.world |= self.debug_world;
+        settings.debug.physics |= self.debug_physics;
+
+        if self.max_enemies.is_some() {
+            settings.debug.max_enemies = self.max_enemies;
+        }
+
+        if self.max_tiles.is_some() {
+            settings.debug.max_tiles = self.max_tiles;
+        }
+
+        if let Some(layers) = &self.layers {
+            settings.debug.layers = layers.clone();
+        }
+    }
+
+    pub fn level_selection(&self) -> LevelSelection {
+        let mut selection = LevelSelection::default();
+
+        if let Some(map) = &self.map {
+            selection.map = map.clone();
+        }
+        selection.level = self.level.clone();
+
+        selection
+    }
+
+    pub fn replay_mode(&self) -> Option<ReplayMode> {
+        if let Some(path) = &self.replay {
+            return Some(ReplayMode::Replay(Recording::load(path)));
+        }
+
+        self.record.as_ref().map(|path| ReplayMode::Record(Some(path.clone())))
+    }
+}
+
+fn parse_window_size(value: &str) -> Result<(u32, u32), String> {
+    let (width, height) = value.split_once('x').ok_or("Expected WIDTHxHEIGHT")?;
+    let width = width.parse().map_err(|e| format!("Invalid width: {e}"))?;
+    let height = height.parse().map_err(|e| format!("Invalid height: {e}"))?;
+
+    Ok((width, height))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overrides_the_settings() {
+        let cli = Cli::try_parse_from([
+            "zelda",
+            "--window-size",
+            "800x600",
+            "--mute",
+            "--layers",
+            "blocks,entities",
+        ])
+        .expect("Valid options");
+        let mut settings = Settings::default();
+
+        cli.apply(&mut settings);
+
+        assert_eq!((settings.window.width, settings.window.height), (800, 600));
+        assert!(settings.volume.muted);
+        assert_eq!(settings.debug.layers, vec![LayerType::Blocks, LayerType::Entities]);
+        assert!(Cli::try_parse_from(["zelda", "--window-size", "800"]).is_err());
+    }
+}
@@ -1,12 +1,21 @@
 use bevy::{math::Vec3Swizzles, prelude::*};
-use bevy_kira_audio::{Audio, AudioControl};
 use bevy_rapier2d::prelude::*;
 use lazy_static::lazy_static;
 
 use crate::{
-    constants::{HIT_DURATION, SPEED},
-    entities::{Attackable, Enemy, HitTimer, Player},
-    events::{DamageAttackable, EmitParticleEffect, KillAttackable, MagicCollision, PlayerCollision, WeaponCollision},
+    audio::PlaySound,
+    constants::{HIT_DURATION, PLAYER_KNOCKBACK, SPEED},
+    entities::{Attackable, Boss, Enemy, GodMode, HitTimer, Player},
+    events::{
+        BossDefeated,
+        DamageAttackable,
+        EmitParticleEffect,
+        KillAttackable,
+        MagicCollision,
+        PlayerCollision,
+        ShakeCamera,
+        WeaponCollision,
+    },
     magic::{Magic, PlayerMagic},
     particles::ParticleEffect,
     weapon::{PlayerWeapon, Weapon},
@@ -17,6 +26,7 @@ use crate::events::DamagePlayer;
 // Weapon: GROUP_2
 // Enemies: GROUP_10
 // Objects: GROUP_20
+// Arena walls: GROUP_20 (blocking only the player and enemies movements)
 
 lazy_static! {
     pub static ref PLAYER_MOVE_COLLISION_GROUP: CollisionGroups =
@@ -30,6 +40,9 @@ lazy_static! {
     pub static ref ENEMY_MOVE_COLLISION_GROUP: CollisionGroups = CollisionGroups::new(Group::GROUP_11, Group::GROUP_20);
     pub static ref OBJECTS_COLLISION_GROUP: CollisionGroups =
         CollisionGroups::new(Group::GROUP_20, Group::GROUP_1 | Group::GROUP_2 | Group::GROUP_3);
+    pub static ref NOCLIP_COLLISION_GROUP: CollisionGroups = CollisionGroups::new(Group::GROUP_1, Group::NONE);
+    pub static ref ARENA_COLLISION_GROUP: CollisionGroups =
+        CollisionGroups::new(Group::GROUP_20, Group::GROUP_1 | Group::GROUP_11);
 }
 
 pub fn handle_collisions(
@@ -152,35 +165,42 @@ fn attack_attackable(
     }
 }
 
+type KilledQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, Option<(&'static Enemy, Option<&'static Boss>)>), With<Attackable>>;
+
 pub fn kill_attackable(
     mut commands: Commands,
     mut player_q: Query<&mut Player>,
-    mut parent_q: Query<&Transform>,
-    attackable_q: Query<Entity, With<Attackable>>,
-    mut enemy_q: Query<&mut Enemy>,
+    attackable_q: KilledQuery,
     mut kill_attackable_reader: EventReader<KillAttackable>,
     mut particle_effect_writer: EventWriter<EmitParticleEffect>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut boss_defeated_writer: EventWriter<BossDefeated>,
+    mut play_sound_writer: EventWriter<PlaySound>,
+    mut shake_camera_writer: EventWriter<ShakeCamera>,
 ) {
     let mut player = player_q.single_mut();
 
     for event in kill_attackable_reader.iter() {
-        if !attackable_q.contains(event.0) {
+        let Ok((transform, enemy)) = attackable_q.get(event.0) else {
             continue;
-        }
-
-        let transform = parent_q.get(event.0).expect("Parent entity must exist");
+        };
 
-        let effect = if let Ok(enemy) = enemy_q.get_mut(event.0) {
+        // The destructible tiles break on their own
+        if let Some((enemy, boss)) = enemy {
             player.add_xp(enemy.xp());
-            audio.play(asset_server.load("audio/death.wav")).with_volume(0.4);
-            ParticleEffect::EnemyDeath(enemy.clone())
-        } else {
-            ParticleEffect::Leaf
-        };
+            play_sound_writer.send(PlaySound::sfx("audio/death.wav").with_volume(0.4).at(transform.translation));
+
+            if boss.is_some() {
+                boss_defeated_writer.send(BossDefeated(event.0));
+                shake_camera_writer.send(ShakeCamera(0.8));
+            } else {
+                shake_camera_writer.send(ShakeCamera(0.15));
+            }
+
+            let effect = ParticleEffect::EnemyDeath(enemy.clone());
+            particle_effect_writer.send(EmitParticleEffect::new(effect, transform.translation));
+        }
 
-        particle_effect_writer.send(EmitParticleEffect::new(effect, transform.translation));
         commands.entity(event.0).despawn_recursive();
     }
 }
@@ -191,8 +211,7 @@ pub fn damage_attackable(
     mut attackable_q: Query<Entity, With<Attackable>>,
     mut enemy_q: Query<(&mut Enemy, &Transform, &mut Velocity)>,
     mut damage_attackable_reader: EventReader<DamageAttackable>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut play_sound_writer: EventWriter<PlaySound>,
 ) {
     let player_transform = player_q.single();
 
@@ -208,7 +227,7 @@ pub fn damage_attackable(
             velocity.linvel = -direction.xy().normalize_or_zero() * enemy.resistance() * SPEED;
 
             enemy.hit();
-            audio.play(asset_server.load("audio/hit.wav")).with_volume(0.4);
+            play_sound_writer.send(PlaySound::sfx("audio/hit.wav").with_volume(0.4).at(enemy_transform.translation));
 
             commands
                 .entity(event.0)
@@ -219,32 +238,62 @@ pub fn damage_attackable(
     }
 }
 
+type HurtPlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static Transform,
+        &'static mut Velocity,
+        Option<&'static HitTimer>,
+        Option<&'static GodMode>,
+    ),
+>;
+
 pub fn damage_player(
     mut commands: Commands,
-    mut player_q: Query<(Entity, &mut Player, &Transform)>,
-    enemy_q: Query<&Enemy>,
+    mut player_q: HurtPlayerQuery,
+    enemy_q: Query<(&Enemy, &Transform, Option<&Boss>)>,
     mut damage_player_reader: EventReader<DamagePlayer>,
     mut particle_effect_writer: EventWriter<EmitParticleEffect>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut play_sound_writer: EventWriter<PlaySound>,
+    mut shake_camera_writer: EventWriter<ShakeCamera>,
 ) {
-    let (player_e, mut player, transform) = player_q.single_mut();
+    let (player_e, mut player, transform, mut velocity, hit_timer, god_mode) = player_q.single_mut();
+    // Both dodging and having just been hit give a few invulnerability frames
+    let mut invulnerable = god_mode.is_some() || hit_timer.is_some() || player.is_dodging();
 
     for event in damage_player_reader.iter() {
-        let Ok(enemy) = enemy_q.get(event.0) else {
+        if invulnerable {
+            continue;
+        }
+
+        let Ok((enemy, enemy_transform, boss)) = enemy_q.get(event.0) else {
             // Not an enemy, bailing out...
             continue;
         };
 
-        player.hit(enemy.damage());
-        audio.play(asset_server.load(enemy.attack_type().sound()));
+        let (damage, attack_type) = match boss {
+            Some(boss) => (boss.damage(enemy), boss.phase.attack_type()),
+            None => (enemy.damage(), enemy.attack_type()),
+        };
+
+        // Bump the player back, like the enemies when they are hit
+        let direction = enemy_transform.translation - transform.translation;
+        velocity.linvel = -direction.xy().normalize_or_zero() * PLAYER_KNOCKBACK * SPEED;
+
+        player.hit(damage);
+        invulnerable = true;
+        shake_camera_writer.send(ShakeCamera(if boss.is_some() { 0.5 } else { 0.3 }));
+        play_sound_writer.send(PlaySound::sfx(attack_type.sound()).at(transform.translation));
 
         commands
             .entity(player_e)
             .insert(HitTimer(Timer::new(HIT_DURATION, TimerMode::Once)));
 
         particle_effect_writer.send(EmitParticleEffect::new(
-            ParticleEffect::EnemyAttack(enemy.clone()),
+            ParticleEffect::EnemyAttack(attack_type),
             transform.translation,
         ));
     }
@@ -0,0 +1,272 @@
+use std::str::FromStr;
+
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+use enum_iterator::all;
+
+use crate::{
+    collisions::{NOCLIP_COLLISION_GROUP, PLAYER_MOVE_COLLISION_GROUP},
+    console::ui::{read_console_output, spawn_console, toggle_console, type_in_console, update_console_ui, Console},
+    constants::TILE_SIZE,
+    entities::{from_position, spawn_enemy, to_position, Enemy, EnemyType, GodMode, Noclip, Player, PlayerStat},
+    events::RestartLevel,
+    frames::TexturePack,
+    screens::is_in_console,
+    AppState,
+    GameAssets,
+};
+
+mod ui;
+
+pub const COMMANDS: [&str; 9] = [
+    "spawn",
+    "give",
+    "god",
+    "noclip",
+    "teleport",
+    "set_stat",
+    "kill_all",
+    "reload_map",
+    "help",
+];
+
+/// A command typed in the console, or sent by a script
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    Spawn(EnemyType),
+    GiveXp(u32),
+    God,
+    Noclip,
+    /// Moves the player to the given tile
+    Teleport(usize, usize),
+    SetStat(PlayerStat, u32),
+    KillAll,
+    ReloadMap,
+    Help,
+}
+
+impl ConsoleCommand {
+    /// The possible values of the argument at `index`, for the given command
+    pub fn arguments(command: &str, index: usize) -> Vec<String> {
+        match (command, index) {
+            ("spawn", 0) => all::<EnemyType>()
+                .filter(|ty| *ty != EnemyType::Boss)
+                .map(|ty| ty.to_string())
+                .collect(),
+            ("give", 0) => vec!["xp".to_string()],
+            ("set_stat", 0) => all::<PlayerStat>()
+                .map(|stat| stat.to_string().to_lowercase())
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl FromStr for ConsoleCommand {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+
+        let number = |index: usize| -> Result<u32, String> {
+            let word = words.get(index).ok_or("Missing number")?;
+            word.parse().map_err(|_| format!("Invalid number {word}"))
+        };
+
+        let command = match words.as_slice() {
+            ["spawn", ty] => Self::Spawn(ty.parse().map_err(|_| format!("Unknown enemy {ty}"))?),
+            ["give", "xp", _] => Self::GiveXp(number(2)?),
+            ["god"] => Self::God,
+            ["noclip"] => Self::Noclip,
+            ["teleport", _, _] => Self::Teleport(number(1)? as usize, number(2)? as usize),
+            ["set_stat", stat, _] => {
+                let stat = all::<PlayerStat>()
+                    .find(|s| s.to_string().eq_ignore_ascii_case(stat))
+                    .ok_or(format!("Unknown stat {stat}"))?;
+
+                Self::SetStat(stat, number(2)?)
+            },
+            ["kill_all"] => Self::KillAll,
+            ["reload_map"] => Self::ReloadMap,
+            ["help"] => Self::Help,
+            [] => return Err("Empty command".to_string()),
+            [command, ..] if COMMANDS.contains(command) => return Err(format!("Invalid arguments for {command}")),
+            [command, ..] => return Err(format!("Unknown command {command}")),
+        };
+
+        Ok(command)
+    }
+}
+
+/// A line printed in the console
+pub struct ConsoleOutput(pub String);
+
+/// The console overlay, to type commands while playing
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .add_startup_system(spawn_console)
+            .add_systems(
+                (
+                    toggle_console,
+                    type_in_console.after(toggle_console).run_if(is_in_console),
+                    read_console_output,
+                    update_console_ui.after(type_in_console).after(read_console_output),
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            );
+    }
+}
+
+type PlayerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static mut Transform,
+        Option<&'static GodMode>,
+        Option<&'static Noclip>,
+    ),
+>;
+
+/// Runs the commands changing the player or the game
+pub fn execute_player_commands(
+    mut commands: Commands,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut player_q: PlayerQuery,
+    mut collider_q: Query<(&Parent, &mut CollisionGroups)>,
+    mut command_reader: EventReader<ConsoleCommand>,
+    mut output_writer: EventWriter<ConsoleOutput>,
+    mut restart_level_writer: EventWriter<RestartLevel>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let (player_e, mut player, mut transform, god_mode, noclip) = player_q.single_mut();
+
+    for command in command_reader.iter() {
+        let output = match command {
+            ConsoleCommand::GiveXp(xp) => {
+                player.add_xp(*xp);
+
+                format!("XP: {}", player.xp)
+            },
+            ConsoleCommand::God => {
+                if god_mode.is_some() {
+                    commands.entity(player_e).remove::<GodMode>();
+                    "God mode off".to_string()
+                } else {
+                    commands.entity(player_e).insert(GodMode);
+                    "God mode on".to_string()
+                }
+            },
+            ConsoleCommand::Noclip => {
+                let groups = if noclip.is_some() {
+                    commands.entity(player_e).remove::<Noclip>();
+                    *PLAYER_MOVE_COLLISION_GROUP
+                } else {
+                    commands.entity(player_e).insert(Noclip);
+                    *NOCLIP_COLLISION_GROUP
+                };
+
+                for (parent, mut collision_groups) in collider_q.iter_mut() {
+                    if parent.get() == player_e {
+                        *collision_groups = groups;
+                    }
+                }
+
+                format!("Noclip {}", if noclip.is_some() { "off" } else { "on" })
+            },
+            ConsoleCommand::Teleport(x, y) => {
+                transform.translation =
+                    from_position((*x as f32 + 0.5) * TILE_SIZE, (*y as f32 + 0.5) * TILE_SIZE, window);
+
+                format!("Teleported to {x}, {y}")
+            },
+            ConsoleCommand::SetStat(stat, value) => {
+                // Beyond what the player has unlocked so far, the limit goes up too
+                if *value > player.stats.limit(*stat) {
+                    player.stats.set_limit(*stat, *value);
+                }
+                player.stats.set(*stat, *value);
+
+                format!("{stat}: {value}")
+            },
+            ConsoleCommand::ReloadMap => {
+                restart_level_writer.send(RestartLevel { reload_map: true });
+
+                "Reloading map".to_string()
+            },
+            ConsoleCommand::Help => format!("Commands: {}", COMMANDS.join(", ")),
+            ConsoleCommand::Spawn(_) | ConsoleCommand::KillAll => continue,
+        };
+
+        output_writer.send(ConsoleOutput(output));
+    }
+}
+
+/// Runs the commands spawning and removing enemies
+pub fn execute_enemy_commands(
+    mut commands: Commands,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<(&Player, &Transform)>,
+    enemy_q: Query<Entity, With<Enemy>>,
+    (assets, atlases, textures): (Res<GameAssets>, Res<Assets<TextureAtlas>>, Res<Assets<TexturePack>>),
+    mut command_reader: EventReader<ConsoleCommand>,
+    mut output_writer: EventWriter<ConsoleOutput>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let (player, transform) = player_q.single();
+
+    for command in command_reader.iter() {
+        let output = match command {
+            ConsoleCommand::Spawn(ty) => {
+                // A couple of tiles in front of the player
+                let offset = player.direction.as_vec2() * 2.0 * TILE_SIZE;
+                let pos = to_position(transform.translation, window) + Vec2::new(offset.x, -offset.y);
+
+                spawn_enemy(&mut commands, window, &assets, &atlases, &textures, *ty, pos.x, pos.y);
+
+                format!("Spawned {ty}")
+            },
+            ConsoleCommand::KillAll => {
+                // Just removed, without any XP or boss reward
+                let mut num_enemies = 0;
+                for enemy in enemy_q.iter() {
+                    commands.entity(enemy).despawn_recursive();
+                    num_enemies += 1;
+                }
+
+                format!("Killed {num_enemies} enemies")
+            },
+            _ => continue,
+        };
+
+        output_writer.send(ConsoleOutput(output));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!("spawn raccoon".parse(), Ok(ConsoleCommand::Spawn(EnemyType::Raccoon)));
+        assert_eq!("give xp 1000".parse(), Ok(ConsoleCommand::GiveXp(1000)));
+        assert_eq!(" teleport 10  20 ".parse(), Ok(ConsoleCommand::Teleport(10, 20)));
+        assert_eq!(
+            "set_stat health 300".parse(),
+            Ok(ConsoleCommand::SetStat(PlayerStat::Health, 300))
+        );
+        assert_eq!("kill_all".parse(), Ok(ConsoleCommand::KillAll));
+        assert!("spawn dragon".parse::<ConsoleCommand>().is_err());
+        assert!("give xp lots".parse::<ConsoleCommand>().is_err());
+        assert!("fly".parse::<ConsoleCommand>().is_err());
+    }
+}
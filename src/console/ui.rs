@@ -0,0 +1,266 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    console::{ConsoleCommand, ConsoleOutput, COMMANDS},
+    constants::{BACK_COLOR, CONSOLE_FONT_SIZE, CONSOLE_HEIGHT, CONSOLE_LINES, MARGIN, TEXT_COLOR},
+    entities::Player,
+    screens::GameMode,
+    Persistent,
+};
+
+#[derive(Component)]
+pub struct ConsoleUi;
+
+#[derive(Component)]
+pub struct ConsoleText;
+
+#[derive(Default, Resource)]
+pub struct Console {
+    input: String,
+    log: Vec<String>,
+    history: Vec<String>,
+    /// The entry of the history being edited, if any
+    history_index: Option<usize>,
+}
+
+impl Console {
+    fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+
+        if self.log.len() > CONSOLE_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    fn submit(&mut self) -> Option<ConsoleCommand> {
+        let line = std::mem::take(&mut self.input);
+        self.history_index = None;
+
+        if line.trim().is_empty() {
+            return None;
+        }
+
+        self.print(format!("> {line}"));
+        self.history.push(line.clone());
+
+        match line.parse() {
+            Ok(command) => Some(command),
+            Err(e) => {
+                self.print(e);
+                None
+            },
+        }
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let last = self.history.len() - 1;
+        self.history_index = match (self.history_index, older) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(index), true) => Some(index.saturating_sub(1)),
+            (Some(index), false) if index < last => Some(index + 1),
+            (Some(_), false) => None,
+        };
+
+        self.input = self
+            .history_index
+            .map(|index| self.history[index].clone())
+            .unwrap_or_default();
+    }
+
+    /// Completes the word being typed, or lists the candidates when there's more than one
+    fn autocomplete(&mut self) {
+        let (input, candidates) = complete(&self.input);
+        self.input = input;
+
+        if candidates.len() > 1 {
+            self.print(candidates.join("  "));
+        }
+    }
+}
+
+/// The completed input, and the candidates for the word being typed
+fn complete(input: &str) -> (String, Vec<String>) {
+    let mut words = input.split_whitespace().collect::<Vec<_>>();
+    if input.is_empty() || input.ends_with(' ') {
+        words.push("");
+    }
+
+    let (current, previous) = words.split_last().expect("There's always a word being typed");
+    let candidates = match previous.split_first() {
+        None => COMMANDS.iter().map(|command| command.to_string()).collect(),
+        Some((command, arguments)) => ConsoleCommand::arguments(command, arguments.len()),
+    }
+    .into_iter()
+    .filter(|candidate| candidate.starts_with(current))
+    .collect::<Vec<_>>();
+
+    let completion = match candidates.as_slice() {
+        [] => return (input.to_string(), candidates),
+        [candidate] => format!("{candidate} "),
+        [first, rest @ ..] => rest.iter().fold(first.clone(), |prefix, candidate| {
+            prefix
+                .chars()
+                .zip(candidate.chars())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect()
+        }),
+    };
+
+    let mut completed = previous.join(" ");
+    if !completed.is_empty() {
+        completed.push(' ');
+    }
+    completed.push_str(&completion);
+
+    (completed, candidates)
+}
+
+pub fn spawn_console(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(CONSOLE_HEIGHT)),
+                    padding: UiRect::all(Val::Px(MARGIN)),
+                    position_type: PositionType::Absolute,
+                    position: UiRect::bottom(Val::Px(0.)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    ..default()
+                },
+                background_color: BACK_COLOR.into(),
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            ConsoleUi,
+            Persistent,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/joystix.ttf"),
+                        font_size: CONSOLE_FONT_SIZE,
+                        color: TEXT_COLOR,
+                    },
+                ),
+                ConsoleText,
+            ));
+        });
+}
+
+pub fn toggle_console(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_mode: ResMut<GameMode>,
+    mut player_q: Query<&mut Velocity, With<Player>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Grave) {
+        return;
+    }
+
+    *game_mode = match *game_mode {
+        GameMode::Playing | GameMode::Map => {
+            // Don't keep walking while typing
+            for mut velocity in player_q.iter_mut() {
+                velocity.linvel = Vec2::ZERO;
+            }
+
+            GameMode::Console
+        },
+        GameMode::Console => GameMode::Playing,
+        _ => return,
+    };
+
+    keyboard_input.reset(KeyCode::Grave);
+}
+
+pub fn type_in_console(
+    mut console: ResMut<Console>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mut character_reader: EventReader<ReceivedCharacter>,
+    mut command_writer: EventWriter<ConsoleCommand>,
+) {
+    for event in character_reader.iter() {
+        if !event.char.is_control() && event.char != '`' {
+            console.input.push(event.char);
+        }
+    }
+
+    for key in keyboard_input.get_just_pressed() {
+        match key {
+            KeyCode::Back => {
+                console.input.pop();
+            },
+            KeyCode::Return => {
+                if let Some(command) = console.submit() {
+                    command_writer.send(command);
+                }
+            },
+            KeyCode::Up => console.browse_history(true),
+            KeyCode::Down => console.browse_history(false),
+            KeyCode::Tab => console.autocomplete(),
+            _ => (),
+        }
+    }
+}
+
+pub fn read_console_output(mut console: ResMut<Console>, mut output_reader: EventReader<ConsoleOutput>) {
+    for output in output_reader.iter() {
+        console.print(&output.0);
+    }
+}
+
+pub fn update_console_ui(
+    console: Res<Console>,
+    game_mode: Res<GameMode>,
+    mut container_q: Query<&mut Visibility, With<ConsoleUi>>,
+    mut text_q: Query<&mut Text, With<ConsoleText>>,
+) {
+    if game_mode.is_changed() {
+        for mut visibility in container_q.iter_mut() {
+            *visibility = if *game_mode == GameMode::Console {
+                Visibility::Inherited
+            } else {
+                Visibility::Hidden
+            };
+        }
+    }
+
+    if !console.is_changed() {
+        return;
+    }
+
+    for mut text in text_q.iter_mut() {
+        let mut lines = console.log.clone();
+        lines.push(format!("> {}_", console.input));
+
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn autocomplete_commands_and_arguments() {
+        assert_eq!(complete("tel").0, "teleport ");
+        assert_eq!(
+            complete("s"),
+            ("s".to_string(), vec!["spawn".to_string(), "set_stat".to_string()])
+        );
+        assert_eq!(complete("spawn ra").0, "spawn raccoon ");
+        assert_eq!(complete("set_stat he").0, "set_stat health ");
+        assert_eq!(complete("give ").0, "give xp ");
+        assert_eq!(complete("fly").0, "fly");
+    }
+}
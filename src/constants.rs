@@ -4,28 +4,83 @@ use std::time::Duration;
 pub const STARTING_XP: u32 = 500;
 pub const SPEED: f32 = 100.0;
 pub const TILE_SIZE: f32 = 64.0;
-pub const SCREEN_WIDTH: u32 = 1280;
-pub const SCREEN_HEIGHT: u32 = 720;
-pub const CAMERA_SCALE: f32 = 2.;
+pub const CAMERA_ZOOM: f32 = 2.;
+pub const MIN_CAMERA_ZOOM: f32 = 1.;
+pub const MAX_CAMERA_ZOOM: f32 = 3.;
+pub const ZOOM_STEP: f32 = 0.25;
+pub const CAMERA_SMOOTHING: f32 = 6.;
+pub const CAMERA_LOOKAHEAD: f32 = 48.;
+pub const MAX_SHAKE_OFFSET: f32 = 24.;
+pub const SHAKE_DECAY: f32 = 1.5;
+pub const SETTINGS_PATH: &str = "settings.json";
+pub const ASSETS_DIR: &str = "assets";
+pub const MAP_MANIFEST: &str = "world.map.json";
+/// Transparent pixels between the frames packed from `assets/images`, so that they don't bleed into each other
+pub const ATLAS_PADDING: u32 = 1;
+pub const ATLAS_MAX_WIDTH: u32 = 2048;
+
+pub const MAX_SOUND_DISTANCE: f32 = 1500.0;
+pub const MAX_SIMULTANEOUS_SOUNDS: usize = 3;
+pub const VOLUME_STEP: f64 = 0.1;
+pub const BOSS_ARENA_SIZE: f32 = 6.0;
+pub const BOSS_CHARGE_FACTOR: f32 = 2.0;
 
 pub const SWITCH_ITEM_DURATION: Duration = Duration::from_millis(200);
 pub const ANIMATION_DURATION: Duration = Duration::from_millis(150);
 pub const ENERGY_RECOVERY_DURATION: Duration = Duration::from_millis(600);
 pub const ATTACK_DURATION: Duration = Duration::from_millis(500);
 pub const HIT_DURATION: Duration = Duration::from_millis(400);
+/// How long the player can't move after being hit, pushed back by the attack
+pub const HIT_INPUT_LOCK: Duration = Duration::from_millis(150);
+pub const PLAYER_KNOCKBACK: f32 = 2.0;
+pub const DODGE_DURATION: Duration = Duration::from_millis(250);
+/// Between the end of a dodge and the next one
+pub const DODGE_COOLDOWN: Duration = Duration::from_millis(600);
+pub const DODGE_COST: u32 = 8;
+/// How much faster than running a dodge is
+pub const DODGE_SPEED_FACTOR: f32 = 2.5;
+pub const FRAME_DURATION: Duration = Duration::from_millis(16);
 
 pub const MARGIN: f32 = 10.;
 pub const PADDING: f32 = 2.;
 pub const HEALTH_BAR_WIDTH: f32 = 200.;
 pub const ENERGY_BAR_WIDTH: f32 = 140.;
+pub const BOSS_BAR_WIDTH: f32 = 400.;
 pub const BAR_WIDTH: f32 = 140.;
 pub const BAR_HEIGHT: f32 = 20.;
 pub const BORDER_WIDTH: f32 = 3.;
 pub const FONT_SIZE: f32 = 18.0;
 pub const ITEM_BOX_SIZE: f32 = 80.0;
+pub const MINIMAP_SIZE: f32 = 160.;
+pub const WORLD_MAP_SIZE: f32 = 0.9;
+pub const MINIMAP_REVEAL_RADIUS: usize = 6;
+pub const MARKER_SIZE: f32 = 6.;
+pub const CONSOLE_HEIGHT: f32 = 40.;
+pub const CONSOLE_LINES: usize = 12;
+pub const CONSOLE_FONT_SIZE: f32 = 14.;
+pub const EDITOR_FONT_SIZE: f32 = 12.;
+pub const PALETTE_ITEM_SIZE: f32 = 48.;
+pub const PALETTE_COLUMNS: usize = 4;
+pub const EDITOR_PAN_SPEED: f32 = 600.;
+/// Depth of the sprites whose foot is at the origin, the lower on the screen the closer
+pub const Y_SORT_DEPTH: f32 = 1000.;
+/// Above every sprite of the map
+pub const EDITOR_DEPTH: f32 = 5000.;
+/// Above the sprites of the map, however tall it is
+pub const AMBIENT_PARTICLES_DEPTH: f32 = 2.;
+pub const FOG_DEPTH: f32 = 1.;
 
 pub const ENERGY_COLOR: Color = Color::rgba(0., 0., 1., 0.9);
 pub const HEALTH_COLOR: Color = Color::rgba(1., 0., 0., 0.9);
+pub const BOSS_HEALTH_COLOR: Color = Color::rgba(0.6, 0., 0.6, 0.9);
+pub const ARENA_WALL_COLOR: Color = Color::rgba(0.6, 0., 0.6, 0.4);
+/// Brightens the glowing particles, slightly see-through
+pub const GLOW_COLOR: Color = Color::rgba(1.6, 1.6, 1.6, 0.8);
+/// The background in broad daylight, tinted with the time of day
+pub const DAY_CLEAR_COLOR: Color = Color::rgb(0.44, 0.87, 0.93); // #70DEEE
+/// Levels of each channel of the day tint, so that the sprites aren't updated every frame
+pub const TINT_STEPS: f32 = 100.;
+pub const FOG_COLOR: Color = Color::rgba(0.85, 0.87, 0.9, 0.7);
 pub const BACK_COLOR: Color = Color::rgba(0.13, 0.13, 0.13, 0.9); // #222222
 pub const SELECTED_BACK_COLOR: Color = Color::rgb(0.93, 0.93, 0.93); // #EEEEEE
 pub const BORDER_COLOR: Color = Color::rgb(0.07, 0.07, 0.07); // #111111
@@ -33,3 +88,19 @@ pub const SELECTED_TEXT_COLOR: Color = Color::rgb(0.07, 0.07, 0.07); // #111111
 pub const TEXT_COLOR: Color = Color::rgb(0.93, 0.93, 0.93); // #EEEEEE
 pub const SELECTED_BAR_COLOR: Color = Color::rgb(0.07, 0.07, 0.07); // #111111
 pub const BAR_COLOR: Color = Color::rgb(0.93, 0.93, 0.93); // #EEEEEE
+pub const PLAYER_MARKER_COLOR: Color = Color::rgb(1., 1., 1.);
+pub const ENEMY_MARKER_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
+pub const GRID_COLOR: Color = Color::rgba(1., 1., 1., 0.25);
+pub const EDITOR_CURSOR_COLOR: Color = Color::rgb(1., 0.85, 0.);
+pub const ERASED_CELL_COLOR: Color = Color::rgba(0.9, 0.1, 0.1, 0.4);
+
+/// How much of the weather changes per second, walking from one region to another
+pub const WEATHER_TRANSITION_SPEED: f32 = 0.25;
+/// How much of their notice radius the enemies lose in heavy rain or thick fog
+pub const RAIN_NOTICE_FACTOR: f32 = 0.3;
+pub const FOG_NOTICE_FACTOR: f32 = 0.5;
+/// Rain that puts out the flames
+pub const RAIN_EXTINGUISH_THRESHOLD: f32 = 0.5;
+/// How far the grass sways in strong wind, as a fraction of its width
+pub const WIND_SWAY: f32 = 0.04;
+pub const WIND_SWAY_SPEED: f32 = 2.;
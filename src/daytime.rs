@@ -0,0 +1,210 @@
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid};
+use serde::Deserialize;
+
+use crate::{
+    constants::{DAY_CLEAR_COLOR, TINT_STEPS},
+    depth::YSort,
+    particles::{Fireflies, ParticleEmitter},
+    Map,
+};
+
+/// The tint of the world at an hour of the day
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Lighting {
+    pub hour: f32,
+    pub tint: [f32; 3],
+}
+
+/// How the days go by
+#[derive(Debug, Clone, Resource, Deserialize, TypeUuid)]
+#[uuid = "85f1b05e-73a9-4adb-bc31-2122b0feafbf"]
+pub struct DayCycle {
+    /// Real seconds for a whole day
+    pub day_duration: f32,
+    pub start_hour: f32,
+    /// Hours the night starts and ends at, when the spawners switch to their night enemies
+    pub dusk: f32,
+    pub dawn: f32,
+    /// Tints of the world at some hours, in order, blended in between
+    pub lighting: Vec<Lighting>,
+}
+
+impl DayCycle {
+    pub fn is_night(&self, hour: f32) -> bool {
+        hour >= self.dusk || hour < self.dawn
+    }
+
+    /// The tint at the given hour, in steps so that the sprites are only updated once in a while
+    pub fn tint(&self, hour: f32) -> Color {
+        let (Some(first), Some(last)) = (self.lighting.first(), self.lighting.last()) else {
+            return Color::WHITE;
+        };
+
+        // Around midnight, it's blended between the last and the first tints of the day
+        let from = self
+            .lighting
+            .iter()
+            .rev()
+            .find(|lighting| lighting.hour <= hour)
+            .unwrap_or(last);
+        let to = self
+            .lighting
+            .iter()
+            .find(|lighting| lighting.hour > hour)
+            .unwrap_or(first);
+
+        let span = (to.hour - from.hour).rem_euclid(24.);
+        let ratio = if span > 0. {
+            (hour - from.hour).rem_euclid(24.) / span
+        } else {
+            0.
+        };
+
+        let [r, g, b] = [0, 1, 2].map(|channel| {
+            let value = from.tint[channel] + (to.tint[channel] - from.tint[channel]) * ratio;
+            (value * TINT_STEPS).round() / TINT_STEPS
+        });
+
+        Color::rgb(r, g, b)
+    }
+}
+
+/// The time in the game, which goes on when the level restarts
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct TimeOfDay {
+    pub hour: f32,
+}
+
+impl TimeOfDay {
+    pub fn new(hour: f32) -> Self {
+        Self { hour }
+    }
+
+    /// The time on a 24 hours clock, e.g. `21:30`
+    pub fn clock(&self) -> String {
+        let minutes = (self.hour * 60.) as u32;
+
+        format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+    }
+}
+
+/// The current time, along with how the days go by
+#[derive(SystemParam)]
+pub struct DayTime<'w> {
+    cycle: Res<'w, DayCycle>,
+    time_of_day: Res<'w, TimeOfDay>,
+}
+
+impl DayTime<'_> {
+    pub fn is_night(&self) -> bool {
+        self.cycle.is_night(self.time_of_day.hour)
+    }
+
+    pub fn tint(&self) -> Color {
+        self.cycle.tint(self.time_of_day.hour)
+    }
+}
+
+/// Sprites that glow in the dark, e.g. the fireflies and the flames, which are not tinted
+#[derive(Component)]
+pub struct Emissive;
+
+pub fn advance_time_of_day(time: Res<Time>, cycle: Res<DayCycle>, mut time_of_day: ResMut<TimeOfDay>) {
+    time_of_day.hour = (time_of_day.hour + time.delta_seconds() * 24. / cycle.day_duration).rem_euclid(24.);
+}
+
+type TintedQuery<'w, 's> = Query<'w, 's, (&'static mut TextureAtlasSprite, Ref<'static, YSort>), Without<Emissive>>;
+type TintedEmitterQuery<'w, 's> =
+    Query<'w, 's, Ref<'static, Handle<ColorMaterial>>, (With<ParticleEmitter>, Without<Emissive>)>;
+
+/// Tints the sprites of the world and the background, without any shader so that it also works everywhere
+pub fn tint_world(
+    mut sprite_q: TintedQuery,
+    mut ground_q: Query<&mut Sprite, With<Map>>,
+    emitter_q: TintedEmitterQuery,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut clear_color: ResMut<ClearColor>,
+    day_time: DayTime,
+    mut current: Local<Option<Color>>,
+) {
+    let tint = day_time.tint();
+    let changed = *current != Some(tint);
+    *current = Some(tint);
+
+    let tinted = |color: Color| Color::rgba(tint.r(), tint.g(), tint.b(), color.a());
+
+    // The new sprites get the current tint, all of them when it changes
+    for (mut sprite, y_sort) in sprite_q.iter_mut() {
+        // Not even touched when already tinted, as it would mark them as changed (e.g. to sort them again)
+        if (changed || y_sort.is_added()) && sprite.color != tinted(sprite.color) {
+            sprite.color = tinted(sprite.color);
+        }
+    }
+
+    for material in emitter_q.iter() {
+        if changed || material.is_added() {
+            if let Some(material) = materials.get_mut(&material) {
+                material.color = tinted(material.color);
+            }
+        }
+    }
+
+    if changed {
+        for mut ground in ground_q.iter_mut() {
+            ground.color = tinted(ground.color);
+        }
+
+        clear_color.0 = Color::rgb(
+            DAY_CLEAR_COLOR.r() * tint.r(),
+            DAY_CLEAR_COLOR.g() * tint.g(),
+            DAY_CLEAR_COLOR.b() * tint.b(),
+        );
+    }
+}
+
+/// The fireflies only come out at night
+pub fn wake_fireflies(mut emitter_q: Query<&mut ParticleEmitter, With<Fireflies>>, day_time: DayTime) {
+    let night = day_time.is_night();
+
+    for mut emitter in emitter_q.iter_mut() {
+        if emitter.active != night {
+            emitter.active = night;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blend_the_lighting() {
+        let cycle = DayCycle {
+            day_duration: 240.,
+            start_hour: 8.,
+            dusk: 20.,
+            dawn: 6.,
+            lighting: vec![
+                Lighting {
+                    hour: 8.,
+                    tint: [1., 1., 1.],
+                },
+                Lighting {
+                    hour: 17.,
+                    tint: [1., 1., 1.],
+                },
+                Lighting {
+                    hour: 20.,
+                    tint: [0.2, 0.2, 0.6],
+                },
+            ],
+        };
+
+        assert_eq!(cycle.tint(14.), Color::WHITE);
+        assert_eq!(cycle.tint(20.), Color::rgb(0.2, 0.2, 0.6));
+        // Halfway between 20:00 and 8:00, past midnight
+        assert_eq!(cycle.tint(2.), Color::rgb(0.6, 0.6, 0.8));
+        assert!(cycle.is_night(2.) && cycle.is_night(21.) && !cycle.is_night(12.));
+        assert_eq!(TimeOfDay::new(21.5).clock(), "21:30");
+    }
+}
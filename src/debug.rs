@@ -1,25 +1,38 @@
-use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 
 use crate::LayerType;
 
-pub const DEBUG_WORLD: bool = false;
-pub const DEBUG_PHYSICS: bool = false;
-pub const MAX_ENEMIES: i32 = -1;
-pub const MAX_TILES: i32 = -1;
-
-lazy_static! {
-    pub static ref VALID_LAYERS: Vec<LayerType> = vec![
-        LayerType::Blocks,
-        LayerType::Grass,
-        LayerType::Objects,
-        LayerType::Entities,
-    ];
+/// Switches to help debugging the game, from the settings file or the command line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugSettings {
+    /// Shows the world inspector
+    pub world: bool,
+    /// Shows the colliders
+    pub physics: bool,
+    pub max_enemies: Option<usize>,
+    pub max_tiles: Option<usize>,
+    /// The map layers to load
+    pub layers: Vec<LayerType>,
 }
 
-pub fn can_spawn(current: usize, max: i32) -> bool {
-    if max == -1 {
-        true
-    } else {
-        current <= max as usize
+impl Default for DebugSettings {
+    fn default() -> Self {
+        Self {
+            world: false,
+            physics: false,
+            max_enemies: None,
+            max_tiles: None,
+            layers: vec![
+                LayerType::Blocks,
+                LayerType::Grass,
+                LayerType::Objects,
+                LayerType::Entities,
+            ],
+        }
     }
 }
+
+pub fn can_spawn(current: usize, max: Option<usize>) -> bool {
+    max.is_none_or(|max| current <= max)
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use serde::Deserialize;
+
+use crate::{
+    constants::{TILE_SIZE, Y_SORT_DEPTH},
+    frames::TexturePack,
+    GameAssets,
+    MapSize,
+};
+
+/// Where the foot of the sprites is, as a fraction of their height from the top, by the start of their frame names
+/// (e.g. `objects` for all the objects, `objects/00` for one of them). Beyond 1 it's below the sprite.
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[uuid = "67f9c1d9-b05b-4aef-aafb-f848705e16fc"]
+pub struct DepthAnchors(HashMap<String, f32>);
+
+impl DepthAnchors {
+    /// The anchor with the longest name matching the frame, the bottom of the sprite by default
+    pub fn get(&self, frame: &str) -> f32 {
+        self.0
+            .iter()
+            .filter(|(name, _)| frame.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+            .map_or(1., |(_, anchor)| *anchor)
+    }
+}
+
+/// Sorts a sprite by its foot point, so that whatever stands lower on the screen is drawn in front
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct YSort {
+    /// Distance of the foot point below the translation, set from the anchors for the sprites of the texture packs
+    pub foot: f32,
+}
+
+impl YSort {
+    pub fn new(foot: f32) -> Self {
+        Self { foot }
+    }
+
+    /// The depth of a sprite at the given height
+    pub fn depth(&self, y: f32) -> f32 {
+        Y_SORT_DEPTH - (y - self.foot)
+    }
+}
+
+/// The depth just above every sorted sprite of the map, which go deeper the lower they stand
+pub fn above_map(size: &MapSize) -> f32 {
+    // Some feet are a little below their sprite
+    Y_SORT_DEPTH + size.height + TILE_SIZE
+}
+
+type AnchoredQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static mut YSort,
+        &'static TextureAtlasSprite,
+        &'static Handle<TextureAtlas>,
+        &'static Transform,
+    ),
+    Or<(Added<YSort>, Changed<TextureAtlasSprite>)>,
+>;
+type MovedQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static YSort),
+    (Without<Parent>, Or<(Changed<Transform>, Changed<YSort>)>),
+>;
+
+/// Places the foot point of the sprites from the texture packs, whenever their frame changes
+pub fn anchor_y_sort(
+    mut sorted_q: AnchoredQuery,
+    assets: Res<GameAssets>,
+    textures: Res<Assets<TexturePack>>,
+    anchors: Res<DepthAnchors>,
+) {
+    for (mut y_sort, sprite, atlas, transform) in sorted_q.iter_mut() {
+        let Some(ty) = assets.asset_type(atlas) else {
+            continue;
+        };
+        let pack = assets.pack(ty, &textures);
+        let Some((name, frame)) = pack.frame_at(sprite.index) else {
+            continue;
+        };
+
+        let foot = (anchors.get(name) - 0.5) * frame.frame.h * transform.scale.y;
+        if y_sort.foot != foot {
+            y_sort.foot = foot;
+        }
+    }
+}
+
+pub fn update_depth(mut sorted_q: MovedQuery) {
+    for (mut transform, y_sort) in sorted_q.iter_mut() {
+        let depth = y_sort.depth(transform.translation.y);
+
+        if transform.translation.z != depth {
+            transform.translation.z = depth;
+        }
+    }
+}
+
+/// Sorts the children (e.g. the weapon of the player) with the rest of the world, not just within their parent
+pub fn update_child_depth(
+    mut sorted_q: Query<(&mut Transform, &YSort, &Parent)>,
+    parent_q: Query<&Transform, Without<Parent>>,
+) {
+    for (mut transform, y_sort, parent) in sorted_q.iter_mut() {
+        let Ok(parent) = parent_q.get(parent.get()) else {
+            continue;
+        };
+
+        let depth = y_sort.depth(parent.translation.y + transform.translation.y) - parent.translation.z;
+        if transform.translation.z != depth {
+            transform.translation.z = depth;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sort_by_foot() {
+        let anchors: DepthAnchors = serde_json::from_str(r#"{"objects": 0.9, "objects/00": 0.75}"#).unwrap();
+        assert_eq!(anchors.get("objects/00.png"), 0.75);
+        assert_eq!(anchors.get("objects/01.png"), 0.9);
+        assert_eq!(anchors.get("player/idle/down.png"), 1.);
+
+        // A tall tree, in front of the player standing behind its foot, behind them once they walk past it
+        let tree = YSort::new((0.9 - 0.5) * 128.);
+        let player = YSort::new(32.);
+        assert!(tree.depth(0.) > player.depth(10.));
+        assert!(tree.depth(0.) < player.depth(-30.));
+    }
+}
@@ -0,0 +1,468 @@
+use anyhow::anyhow;
+use bevy::{prelude::*, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+
+use crate::{
+    camera::CameraController,
+    constants::EDITOR_PAN_SPEED,
+    editor::ui::{select_brush, show_editor_ui, update_cursor, update_palette, update_previews, update_status},
+    entities::{from_position, to_cell, to_position, EnemyType, Player},
+    events::RestartLevel,
+    map::{LayerType, LevelSelection, MapAssets, MapManifest, WorldMap},
+    screens::{is_editing, GameMode},
+    AppState,
+    MapSize,
+};
+
+mod ui;
+
+const EMPTY_CELL: i32 = -1;
+const PLAYER_CELL: i32 = 394;
+
+/// A value to paint in the cells of a layer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    pub layer: LayerType,
+    pub cell: i32,
+}
+
+impl Brush {
+    /// Every value of the map, grouped by layer
+    fn all() -> Vec<Self> {
+        let layers = [
+            (LayerType::Blocks, vec![395]),
+            (LayerType::Grass, (8..=10).collect()),
+            (LayerType::Objects, (0..=20).collect()),
+            (LayerType::Entities, vec![PLAYER_CELL, 390, 391, 392, 393, 396]),
+        ];
+
+        layers
+            .into_iter()
+            .flat_map(|(layer, cells)| cells.into_iter().map(move |cell| Self { layer, cell }))
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        match (self.layer, self.cell) {
+            (LayerType::Blocks, _) => "block".to_string(),
+            (LayerType::Grass, cell) => format!("grass {}", cell - 7),
+            (LayerType::Objects, cell) => format!("object {cell}"),
+            (LayerType::Entities, PLAYER_CELL) => "player".to_string(),
+            (LayerType::Entities, cell) => EnemyType::from(cell).to_string(),
+        }
+    }
+}
+
+/// A change to a cell of the map
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellEdit {
+    pub layer: LayerType,
+    pub row: usize,
+    pub col: usize,
+    pub before: i32,
+    pub after: i32,
+}
+
+impl CellEdit {
+    fn reverted(self) -> Self {
+        Self {
+            before: self.after,
+            after: self.before,
+            ..self
+        }
+    }
+
+    fn apply(&self, world_map: &mut WorldMap) {
+        world_map.set(self.layer, self.row, self.col, self.after);
+    }
+}
+
+/// Cells changed in the map, shown over the level until it's spawned again
+pub struct MapEdited(pub Vec<CellEdit>);
+
+#[derive(Resource)]
+pub struct Editor {
+    brushes: Vec<Brush>,
+    selected: usize,
+    /// The edits of each stroke, from the oldest
+    undo: Vec<Vec<CellEdit>>,
+    redo: Vec<Vec<CellEdit>>,
+    /// The edits of the stroke being painted
+    stroke: Vec<CellEdit>,
+    /// Whether the map changed since the level was spawned
+    dirty: bool,
+    status: String,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            brushes: Brush::all(),
+            selected: 0,
+            undo: vec![],
+            redo: vec![],
+            stroke: vec![],
+            dirty: false,
+            status: String::new(),
+        }
+    }
+}
+
+impl Editor {
+    pub fn brush(&self) -> Brush {
+        self.brushes[self.selected]
+    }
+
+    /// Paints a cell with the brush, or empties it, returning what changed
+    fn paint(&mut self, world_map: &mut WorldMap, row: usize, col: usize, erase: bool) -> Vec<CellEdit> {
+        let brush = self.brush();
+        let cell = if erase { EMPTY_CELL } else { brush.cell };
+        let mut edits = vec![];
+
+        // There's only one player, moving it clears its previous cell
+        if brush.layer == LayerType::Entities && cell == PLAYER_CELL {
+            let players = world_map
+                .layers
+                .get(&LayerType::Entities)
+                .map(|layer| cells_with(&layer.data, PLAYER_CELL))
+                .unwrap_or_default();
+
+            for (player_row, player_col) in players {
+                if (player_row, player_col) != (row, col) {
+                    edits.extend(set_cell(
+                        world_map,
+                        LayerType::Entities,
+                        player_row,
+                        player_col,
+                        EMPTY_CELL,
+                    ));
+                }
+            }
+        }
+
+        edits.extend(set_cell(world_map, brush.layer, row, col, cell));
+
+        self.stroke.extend(edits.iter().copied());
+        self.dirty |= !edits.is_empty();
+
+        edits
+    }
+
+    fn end_stroke(&mut self) {
+        if self.stroke.is_empty() {
+            return;
+        }
+
+        self.undo.push(std::mem::take(&mut self.stroke));
+        self.redo.clear();
+    }
+
+    fn undo(&mut self, world_map: &mut WorldMap) -> Vec<CellEdit> {
+        self.end_stroke();
+
+        let Some(stroke) = self.undo.pop() else {
+            return vec![];
+        };
+
+        let edits = stroke.iter().rev().map(|edit| edit.reverted()).collect::<Vec<_>>();
+        for edit in &edits {
+            edit.apply(world_map);
+        }
+
+        self.redo.push(stroke);
+        self.dirty = true;
+
+        edits
+    }
+
+    fn redo(&mut self, world_map: &mut WorldMap) -> Vec<CellEdit> {
+        let Some(stroke) = self.redo.pop() else {
+            return vec![];
+        };
+
+        for edit in &stroke {
+            edit.apply(world_map);
+        }
+
+        self.undo.push(stroke.clone());
+        self.dirty = true;
+
+        stroke
+    }
+
+    /// Writes the loaded layers back to the files of the map
+    fn save(&mut self, world_map: &WorldMap, selection: &LevelSelection, manifest: &MapManifest) {
+        let result = world_map.layers.iter().try_for_each(|(ty, layer)| {
+            let file_name = manifest.layers.get(ty).ok_or(anyhow!("No file for the {ty:?} layer"))?;
+
+            layer.save(&selection.path(file_name))
+        });
+
+        self.status = match result {
+            Ok(_) => {
+                info!("Map saved to {}", selection.map);
+                "Saved".to_string()
+            },
+            Err(e) => {
+                warn!("Cannot save map to {}: {e}", selection.map);
+                "Cannot save".to_string()
+            },
+        };
+    }
+}
+
+fn set_cell(world_map: &mut WorldMap, layer: LayerType, row: usize, col: usize, cell: i32) -> Option<CellEdit> {
+    let before = world_map.set(layer, row, col, cell)?;
+
+    (before != cell).then_some(CellEdit {
+        layer,
+        row,
+        col,
+        before,
+        after: cell,
+    })
+}
+
+fn cells_with(data: &[Vec<i32>], value: i32) -> Vec<(usize, usize)> {
+    data.iter()
+        .enumerate()
+        .flat_map(|(row, cells)| {
+            cells
+                .iter()
+                .enumerate()
+                .filter(move |(_, &cell)| cell == value)
+                .map(move |(col, _)| (row, col))
+        })
+        .collect()
+}
+
+/// The cell of the map under the mouse
+fn hovered_cell(
+    window: &Window,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    world_map: &WorldMap,
+) -> Option<(usize, usize)> {
+    let cursor = window.cursor_position()?;
+    let ray = camera.viewport_to_world(camera_transform, cursor)?;
+    let (row, col) = to_cell(to_position(ray.origin, window))?;
+    let (rows, cols) = world_map.size();
+
+    (row < rows && col < cols).then_some((row, col))
+}
+
+/// Paints the layers of the map over the running level, and saves them back to its files
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Editor>()
+            .add_event::<MapEdited>()
+            .add_systems(
+                (
+                    toggle_editor,
+                    show_editor_ui.after(toggle_editor),
+                    pan_camera.run_if(is_editing),
+                    select_brush.run_if(is_editing),
+                    paint_cells.after(select_brush).run_if(is_editing),
+                    edit_history.run_if(is_editing),
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    update_previews.after(paint_cells).after(edit_history),
+                    update_cursor.run_if(is_editing),
+                    update_palette.run_if(is_editing),
+                    update_status.after(edit_history).run_if(is_editing),
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            );
+    }
+}
+
+fn toggle_editor(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut game_mode: ResMut<GameMode>,
+    mut editor: ResMut<Editor>,
+    mut camera_q: Query<&mut CameraController>,
+    mut player_q: Query<(&Transform, &mut Velocity), With<Player>>,
+    mut restart_level_writer: EventWriter<RestartLevel>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+
+    let mut controller = camera_q.single_mut();
+
+    *game_mode = match *game_mode {
+        GameMode::Playing => {
+            for (transform, mut velocity) in player_q.iter_mut() {
+                velocity.linvel = Vec2::ZERO;
+                controller.focus = Some(transform.translation.truncate());
+            }
+
+            GameMode::Editing
+        },
+        GameMode::Editing => {
+            editor.end_stroke();
+            controller.focus = None;
+
+            // Spawn the edited map
+            if editor.dirty {
+                editor.dirty = false;
+                restart_level_writer.send(RestartLevel { reload_map: false });
+            }
+
+            GameMode::Playing
+        },
+        _ => return,
+    };
+
+    keyboard_input.reset(KeyCode::F2);
+}
+
+fn pan_camera(
+    time: Res<Time>,
+    size: Res<MapSize>,
+    keyboard_input: Res<Input<KeyCode>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut camera_q: Query<&mut CameraController>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    let mut direction = Vec2::ZERO;
+    for key in keyboard_input.get_pressed() {
+        match key {
+            KeyCode::Left => direction.x -= 1.0,
+            KeyCode::Right => direction.x += 1.0,
+            KeyCode::Up => direction.y += 1.0,
+            KeyCode::Down => direction.y -= 1.0,
+            _ => (),
+        }
+    }
+
+    let mut controller = camera_q.single_mut();
+    let Some(focus) = controller.focus else {
+        return;
+    };
+
+    let min = from_position(0.0, size.height, window).truncate();
+    let max = from_position(size.width, 0.0, window).truncate();
+    let focus = focus + direction * EDITOR_PAN_SPEED * controller.zoom * time.delta_seconds();
+
+    controller.focus = Some(focus.clamp(min, max));
+}
+
+type EditorUiQuery<'w, 's> = Query<'w, 's, &'static Interaction, Or<(With<ui::EditorUi>, With<ui::PaletteButton>)>>;
+
+fn paint_cells(
+    mouse_input: Res<Input<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    interaction_q: EditorUiQuery,
+    mut editor: ResMut<Editor>,
+    mut world_map: ResMut<WorldMap>,
+    mut map_edited_writer: EventWriter<MapEdited>,
+) {
+    if mouse_input.any_just_released([MouseButton::Left, MouseButton::Right]) {
+        editor.end_stroke();
+    }
+
+    let erase = mouse_input.pressed(MouseButton::Right);
+    if !erase && !mouse_input.pressed(MouseButton::Left) {
+        return;
+    }
+
+    // Don't paint under the palette
+    if interaction_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+
+    let Some((row, col)) = hovered_cell(window, camera, camera_transform, &world_map) else {
+        return;
+    };
+
+    let edits = editor.paint(&mut world_map, row, col, erase);
+    if !edits.is_empty() {
+        map_edited_writer.send(MapEdited(edits));
+    }
+}
+
+fn edit_history(
+    keyboard_input: Res<Input<KeyCode>>,
+    selection: Res<LevelSelection>,
+    map_assets: Res<MapAssets>,
+    manifests: Res<Assets<MapManifest>>,
+    mut editor: ResMut<Editor>,
+    mut world_map: ResMut<WorldMap>,
+    mut map_edited_writer: EventWriter<MapEdited>,
+) {
+    if !keyboard_input.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
+        return;
+    }
+
+    let edits = if keyboard_input.just_pressed(KeyCode::Z) {
+        editor.undo(&mut world_map)
+    } else if keyboard_input.just_pressed(KeyCode::Y) {
+        editor.redo(&mut world_map)
+    } else {
+        if keyboard_input.just_pressed(KeyCode::S) {
+            if let Some(manifest) = manifests.get(&map_assets.manifest) {
+                editor.end_stroke();
+                editor.save(&world_map, &selection, manifest);
+            }
+        }
+
+        return;
+    };
+
+    if !edits.is_empty() {
+        map_edited_writer.send(MapEdited(edits));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paint_undo_and_redo() {
+        let mut world_map = WorldMap::debug_enemy();
+        let mut editor = Editor::default();
+        let player = |world_map: &WorldMap| cells_with(&world_map.layers[&LayerType::Entities].data, PLAYER_CELL);
+
+        editor.selected = editor
+            .brushes
+            .iter()
+            .position(|brush| brush.cell == PLAYER_CELL)
+            .unwrap();
+        editor.paint(&mut world_map, 5, 1, false);
+        editor.paint(&mut world_map, 6, 1, false);
+        editor.end_stroke();
+        assert_eq!(player(&world_map), vec![(6, 1)]);
+
+        editor.paint(&mut world_map, 8, 2, true);
+        editor.end_stroke();
+        assert!(world_map.is_free(8, 2, &[LayerType::Entities]));
+
+        editor.undo(&mut world_map);
+        assert_eq!(world_map.layers[&LayerType::Entities].get(8, 2), Some(391));
+
+        editor.undo(&mut world_map);
+        assert_eq!(player(&world_map), vec![(2, 2)]);
+
+        editor.redo(&mut world_map);
+        assert_eq!(player(&world_map), vec![(6, 1)]);
+    }
+}
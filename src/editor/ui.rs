@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+
+use bevy::{prelude::*, sprite::Anchor, window::PrimaryWindow};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::{
+    constants::{
+        BACK_COLOR,
+        BORDER_COLOR,
+        EDITOR_CURSOR_COLOR,
+        EDITOR_DEPTH,
+        EDITOR_FONT_SIZE,
+        ERASED_CELL_COLOR,
+        GRID_COLOR,
+        MARGIN,
+        PADDING,
+        PALETTE_COLUMNS,
+        PALETTE_ITEM_SIZE,
+        SELECTED_BACK_COLOR,
+        TEXT_COLOR,
+        TILE_SIZE,
+    },
+    editor::{hovered_cell, Brush, Editor, MapEdited, EMPTY_CELL, PLAYER_CELL},
+    entities::{from_position, EnemyType},
+    frames::TexturePack,
+    map::{LayerType, WorldMap},
+    screens::GameMode,
+    widgets::{AtlasImageBundle, UiAtlasImage},
+    GameAssetType,
+    GameAssets,
+    Persistent,
+};
+
+/// Removed when leaving the editor, while the level may be spawned again
+#[derive(Component)]
+pub struct EditorUi;
+
+#[derive(Component)]
+pub struct PaletteButton(usize);
+
+#[derive(Component)]
+pub struct EditorCursor;
+
+#[derive(Component)]
+pub struct EditorStatus;
+
+/// An edited cell, drawn over the level
+#[derive(Component)]
+pub struct CellPreview {
+    layer: LayerType,
+    row: usize,
+    col: usize,
+}
+
+enum BrushImage {
+    Atlas(Handle<TextureAtlas>, usize),
+    Image(Handle<Image>),
+}
+
+/// How a value of the map is rendered, if it's known
+fn brush_image(
+    brush: Brush,
+    asset_server: &AssetServer,
+    assets: &GameAssets,
+    textures: &Assets<TexturePack>,
+) -> Option<BrushImage> {
+    let image = match (brush.layer, brush.cell) {
+        (LayerType::Blocks, 395) => BrushImage::Image(asset_server.load("test/rock.png")),
+        (LayerType::Grass, cell @ 8..=10) => {
+            BrushImage::Atlas(assets.get(GameAssetType::Grass).clone(), (cell - 8) as usize)
+        },
+        (LayerType::Objects, cell @ 0..=20) => {
+            BrushImage::Atlas(assets.get(GameAssetType::Objects).clone(), cell as usize)
+        },
+        (LayerType::Entities, PLAYER_CELL) => BrushImage::Atlas(assets.get(GameAssetType::Player).clone(), 0),
+        (LayerType::Entities, cell @ (390..=393 | 396)) => {
+            let name = format!("monsters/{}/idle/00.png", EnemyType::from(cell).sprite());
+            let pack = assets.pack(GameAssetType::Monsters, textures);
+
+            BrushImage::Atlas(assets.get(GameAssetType::Monsters).clone(), pack.index_of(&name))
+        },
+        _ => return None,
+    };
+
+    Some(image)
+}
+
+pub fn show_editor_ui(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    editor: Res<Editor>,
+    world_map: Res<WorldMap>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    ui_q: Query<Entity, With<EditorUi>>,
+    (asset_server, assets, textures): (Res<AssetServer>, Res<GameAssets>, Res<Assets<TexturePack>>),
+) {
+    if !game_mode.is_changed() {
+        return;
+    }
+
+    if *game_mode != GameMode::Editing {
+        for entity in ui_q.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    if !ui_q.is_empty() {
+        return;
+    }
+
+    spawn_grid(&mut commands, window, &world_map);
+
+    commands.spawn((
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shapes::Rectangle {
+                extents: Vec2::splat(TILE_SIZE),
+                origin: RectangleOrigin::Center,
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Stroke::new(EDITOR_CURSOR_COLOR, 3.0),
+        EditorCursor,
+        EditorUi,
+        Persistent,
+    ));
+
+    let font = asset_server.load("fonts/joystix.ttf");
+    let palette_width = PALETTE_COLUMNS as f32 * (PALETTE_ITEM_SIZE + 2. * PADDING);
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        left: Val::Px(MARGIN),
+                        top: Val::Px(MARGIN),
+                        ..default()
+                    },
+                    size: Size::width(Val::Px(palette_width + 2. * MARGIN)),
+                    padding: UiRect::all(Val::Px(MARGIN)),
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
+                background_color: BACK_COLOR.into(),
+                z_index: ZIndex::Global(5),
+                ..default()
+            },
+            Interaction::default(),
+            EditorUi,
+            Persistent,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font,
+                        font_size: EDITOR_FONT_SIZE,
+                        color: TEXT_COLOR,
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(MARGIN)),
+                    ..default()
+                }),
+                EditorStatus,
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_wrap: FlexWrap::Wrap,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (index, brush) in editor.brushes.iter().enumerate() {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        size: Size::all(Val::Px(PALETTE_ITEM_SIZE)),
+                                        margin: UiRect::all(Val::Px(PADDING)),
+                                        padding: UiRect::all(Val::Px(PADDING)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: BORDER_COLOR.into(),
+                                    ..default()
+                                },
+                                PaletteButton(index),
+                            ))
+                            .with_children(|parent| {
+                                let style = Style {
+                                    max_size: Size::all(Val::Percent(100.)),
+                                    ..default()
+                                };
+
+                                match brush_image(*brush, &asset_server, &assets, &textures) {
+                                    Some(BrushImage::Atlas(atlas, index)) => {
+                                        parent.spawn(AtlasImageBundle {
+                                            style,
+                                            atlas_image: UiAtlasImage::new(atlas, index),
+                                            ..default()
+                                        });
+                                    },
+                                    Some(BrushImage::Image(image)) => {
+                                        parent.spawn(ImageBundle {
+                                            style,
+                                            image: image.into(),
+                                            ..default()
+                                        });
+                                    },
+                                    None => (),
+                                }
+                            });
+                    }
+                });
+        });
+}
+
+fn spawn_grid(commands: &mut Commands, window: &Window, world_map: &WorldMap) {
+    let (rows, cols) = world_map.size();
+    let corner =
+        |row: usize, col: usize| from_position(col as f32 * TILE_SIZE, row as f32 * TILE_SIZE, window).truncate();
+
+    let mut path = PathBuilder::new();
+    for row in 0..=rows {
+        path.move_to(corner(row, 0));
+        path.line_to(corner(row, cols));
+    }
+    for col in 0..=cols {
+        path.move_to(corner(0, col));
+        path.line_to(corner(rows, col));
+    }
+
+    commands.spawn((
+        ShapeBundle {
+            path: path.build(),
+            transform: Transform::from_xyz(0.0, 0.0, EDITOR_DEPTH),
+            ..default()
+        },
+        Stroke::new(GRID_COLOR, 1.0),
+        EditorUi,
+        Persistent,
+    ));
+}
+
+pub fn select_brush(mut editor: ResMut<Editor>, button_q: Query<(&PaletteButton, &Interaction), Changed<Interaction>>) {
+    for (button, interaction) in button_q.iter() {
+        if *interaction == Interaction::Clicked {
+            editor.selected = button.0;
+            editor.status.clear();
+        }
+    }
+}
+
+pub fn update_cursor(
+    world_map: Res<WorldMap>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Camera, &GlobalTransform)>,
+    mut cursor_q: Query<(&mut Transform, &mut Visibility), With<EditorCursor>>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_q.single();
+
+    for (mut transform, mut visibility) in cursor_q.iter_mut() {
+        match hovered_cell(window, camera, camera_transform, &world_map) {
+            Some((row, col)) => {
+                let pos = from_position((col as f32 + 0.5) * TILE_SIZE, (row as f32 + 0.5) * TILE_SIZE, window);
+                transform.translation = pos.truncate().extend(EDITOR_DEPTH + 1.0);
+                *visibility = Visibility::Inherited;
+            },
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+pub fn update_palette(editor: Res<Editor>, mut button_q: Query<(&PaletteButton, &Interaction, &mut BackgroundColor)>) {
+    for (button, interaction, mut background) in button_q.iter_mut() {
+        let color = if button.0 == editor.selected {
+            SELECTED_BACK_COLOR
+        } else if *interaction == Interaction::Hovered {
+            GRID_COLOR
+        } else {
+            BORDER_COLOR
+        };
+
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+pub fn update_status(editor: Res<Editor>, world_map: Res<WorldMap>, mut text_q: Query<&mut Text, With<EditorStatus>>) {
+    if !editor.is_changed() {
+        return;
+    }
+
+    let brush = editor.brush();
+    let layer = if world_map.layers.contains_key(&brush.layer) {
+        format!("{:?}", brush.layer)
+    } else {
+        format!("{:?} (not loaded)", brush.layer)
+    };
+
+    let lines = [
+        format!("{layer}: {}", brush.name()),
+        "Left click: paint".to_string(),
+        "Right click: erase".to_string(),
+        "Ctrl+Z/Y: undo/redo".to_string(),
+        "Ctrl+S: save".to_string(),
+        "F2: play".to_string(),
+        editor.status.clone(),
+    ];
+
+    for mut text in text_q.iter_mut() {
+        text.sections[0].value = lines.join("\n");
+    }
+}
+
+pub fn update_previews(
+    mut commands: Commands,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    preview_q: Query<(Entity, &CellPreview)>,
+    mut map_edited_reader: EventReader<MapEdited>,
+    (asset_server, assets, textures): (Res<AssetServer>, Res<GameAssets>, Res<Assets<TexturePack>>),
+) {
+    // Only the last value of each cell matters
+    let cells = map_edited_reader
+        .iter()
+        .flat_map(|edited| edited.0.iter())
+        .map(|edit| ((edit.layer, edit.row, edit.col), edit.after))
+        .collect::<HashMap<_, _>>();
+
+    if cells.is_empty() {
+        return;
+    }
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    for (entity, preview) in preview_q.iter() {
+        if cells.contains_key(&(preview.layer, preview.row, preview.col)) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    for ((layer, row, col), cell) in cells {
+        // Standing on the bottom of the cell, like the tiles
+        let pos = from_position((col as f32 + 0.5) * TILE_SIZE, (row as f32 + 1.0) * TILE_SIZE, window);
+        let transform = Transform::from_translation(pos.truncate().extend(EDITOR_DEPTH - 1.0));
+
+        let mut cmd = if cell == EMPTY_CELL {
+            commands.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: ERASED_CELL_COLOR,
+                    custom_size: Some(Vec2::splat(TILE_SIZE)),
+                    anchor: Anchor::BottomCenter,
+                    ..default()
+                },
+                transform,
+                ..default()
+            })
+        } else {
+            match brush_image(Brush { layer, cell }, &asset_server, &assets, &textures) {
+                Some(BrushImage::Atlas(atlas, index)) => commands.spawn(SpriteSheetBundle {
+                    sprite: TextureAtlasSprite {
+                        index,
+                        anchor: Anchor::BottomCenter,
+                        ..default()
+                    },
+                    texture_atlas: atlas,
+                    transform,
+                    ..default()
+                }),
+                Some(BrushImage::Image(image)) => commands.spawn(SpriteBundle {
+                    sprite: Sprite {
+                        anchor: Anchor::BottomCenter,
+                        ..default()
+                    },
+                    texture: image,
+                    transform,
+                    ..default()
+                }),
+                None => continue,
+            }
+        };
+
+        cmd.insert((CellPreview { layer, row, col }, EditorUi, Persistent));
+    }
+}
@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use bevy::{math::Vec3Swizzles, prelude::*};
+use bevy_rapier2d::prelude::*;
+use parse_display::Display;
+
+use crate::{
+    collisions::ARENA_COLLISION_GROUP,
+    constants::{ARENA_WALL_COLOR, BOSS_ARENA_SIZE, TILE_SIZE},
+    depth::YSort,
+    entities::{AttackType, Attackable, Enemy, Player},
+    events::{BossDefeated, EmitParticleEffect},
+    particles::ParticleEffect,
+};
+
+#[derive(Debug, Clone, Copy, Display, PartialEq, Reflect)]
+#[display(style = "snake_case")]
+pub enum BossPhase {
+    Melee,
+    Charge,
+    Storm,
+}
+
+impl BossPhase {
+    pub fn from_health_ratio(ratio: f32) -> Self {
+        if ratio > 2.0 / 3.0 {
+            BossPhase::Melee
+        } else if ratio > 1.0 / 3.0 {
+            BossPhase::Charge
+        } else {
+            BossPhase::Storm
+        }
+    }
+
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            BossPhase::Melee => 1.0,
+            BossPhase::Charge => 1.5,
+            BossPhase::Storm => 0.5,
+        }
+    }
+
+    pub fn damage_factor(&self) -> f32 {
+        match self {
+            BossPhase::Melee => 1.0,
+            BossPhase::Charge => 1.5,
+            BossPhase::Storm => 0.75,
+        }
+    }
+
+    pub fn attack_radius(&self) -> f32 {
+        match self {
+            BossPhase::Melee => 140.0,
+            BossPhase::Charge => 320.0,
+            BossPhase::Storm => 480.0,
+        }
+    }
+
+    pub fn attack_cooldown(&self) -> Duration {
+        let ms = match self {
+            BossPhase::Melee => 800,
+            BossPhase::Charge => 600,
+            BossPhase::Storm => 1500,
+        };
+
+        Duration::from_millis(ms)
+    }
+
+    pub fn attack_type(&self) -> AttackType {
+        match self {
+            BossPhase::Melee => AttackType::Claw,
+            BossPhase::Charge => AttackType::Slash,
+            BossPhase::Storm => AttackType::Thunder,
+        }
+    }
+}
+
+#[derive(Component, Reflect)]
+pub struct Boss {
+    pub phase: BossPhase,
+    pub active: bool,
+}
+
+impl Default for Boss {
+    fn default() -> Self {
+        Self {
+            phase: BossPhase::Melee,
+            active: false,
+        }
+    }
+}
+
+impl Boss {
+    pub fn damage(&self, enemy: &Enemy) -> u32 {
+        (enemy.damage() as f32 * self.phase.damage_factor()).round() as u32
+    }
+}
+
+#[derive(Component)]
+pub struct BossArena {
+    boss: Entity,
+    bounds: Rect,
+    locked: bool,
+}
+
+#[derive(Component)]
+pub struct ArenaWall(Entity);
+
+pub fn spawn_boss_arena(commands: &mut Commands, boss: Entity, center: Vec2) {
+    let half_size = Vec2::splat(BOSS_ARENA_SIZE * TILE_SIZE);
+
+    commands.entity(boss).insert(Boss::default());
+    commands.spawn(BossArena {
+        boss,
+        bounds: Rect::from_center_half_size(center, half_size),
+        locked: false,
+    });
+}
+
+pub fn lock_boss_arena(
+    mut commands: Commands,
+    player_q: Query<&Transform, With<Player>>,
+    mut arena_q: Query<(Entity, &mut BossArena)>,
+    mut boss_q: Query<&mut Boss>,
+) {
+    let Ok(player_transform) = player_q.get_single() else {
+        return;
+    };
+
+    let pos = player_transform.translation.xy();
+
+    for (entity, mut arena) in arena_q.iter_mut() {
+        if arena.locked || !arena.bounds.contains(pos) {
+            continue;
+        }
+
+        let Ok(mut boss) = boss_q.get_mut(arena.boss) else {
+            continue;
+        };
+
+        boss.active = true;
+        arena.locked = true;
+
+        spawn_arena_walls(&mut commands, entity, arena.bounds);
+    }
+}
+
+fn spawn_arena_walls(commands: &mut Commands, arena: Entity, bounds: Rect) {
+    let thickness = TILE_SIZE / 4.0;
+    let center = bounds.center();
+    let size = bounds.size();
+
+    let walls = [
+        (Vec2::new(center.x, bounds.max.y), Vec2::new(size.x, thickness)),
+        (Vec2::new(center.x, bounds.min.y), Vec2::new(size.x, thickness)),
+        (Vec2::new(bounds.min.x, center.y), Vec2::new(thickness, size.y)),
+        (Vec2::new(bounds.max.x, center.y), Vec2::new(thickness, size.y)),
+    ];
+
+    for (pos, size) in walls {
+        let y_sort = YSort::new(size.y / 2.0);
+
+        commands.spawn((
+            SpriteBundle {
+                sprite: Sprite {
+                    color: ARENA_WALL_COLOR,
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos.extend(y_sort.depth(pos.y))),
+                ..default()
+            },
+            y_sort,
+            RigidBody::Fixed,
+            Collider::cuboid(size.x / 2.0, size.y / 2.0),
+            *ARENA_COLLISION_GROUP,
+            ColliderDebugColor(Color::PURPLE),
+            ArenaWall(arena),
+        ));
+    }
+}
+
+pub fn update_boss_phase(
+    mut boss_q: Query<(&mut Boss, &Enemy, &Attackable, &Transform), Changed<Attackable>>,
+    mut particle_effect_writer: EventWriter<EmitParticleEffect>,
+) {
+    for (mut boss, enemy, attackable, transform) in boss_q.iter_mut() {
+        let ratio = attackable.health() as f32 / enemy.health() as f32;
+        let phase = BossPhase::from_health_ratio(ratio);
+
+        if phase != boss.phase {
+            info!("Boss entering {phase} phase");
+
+            boss.phase = phase;
+            particle_effect_writer.send(EmitParticleEffect::new(ParticleEffect::Aura, transform.translation));
+        }
+    }
+}
+
+/// Opens the arenas whose boss is gone without being defeated, e.g. removed from the console
+pub fn remove_empty_arenas(
+    mut commands: Commands,
+    arena_q: Query<(Entity, &BossArena)>,
+    wall_q: Query<(Entity, &ArenaWall)>,
+    boss_q: Query<(), With<Boss>>,
+) {
+    for (arena_e, arena) in arena_q.iter() {
+        if boss_q.contains(arena.boss) {
+            continue;
+        }
+
+        for (wall_e, wall) in wall_q.iter() {
+            if wall.0 == arena_e {
+                commands.entity(wall_e).despawn_recursive();
+            }
+        }
+
+        commands.entity(arena_e).despawn();
+    }
+}
+
+pub fn reward_boss_defeat(
+    mut commands: Commands,
+    mut player_q: Query<(&mut Player, &Transform)>,
+    arena_q: Query<(Entity, &BossArena)>,
+    wall_q: Query<(Entity, &ArenaWall)>,
+    mut boss_defeated_reader: EventReader<BossDefeated>,
+    mut particle_effect_writer: EventWriter<EmitParticleEffect>,
+) {
+    for event in boss_defeated_reader.iter() {
+        for (arena_e, arena) in arena_q.iter() {
+            if arena.boss != event.0 {
+                continue;
+            }
+
+            for (wall_e, wall) in wall_q.iter() {
+                if wall.0 == arena_e {
+                    commands.entity(wall_e).despawn_recursive();
+                }
+            }
+
+            commands.entity(arena_e).despawn();
+        }
+
+        let (mut player, transform) = player_q.single_mut();
+        player.restore();
+
+        particle_effect_writer.send(EmitParticleEffect::new(ParticleEffect::Aura, transform.translation));
+        particle_effect_writer.send(EmitParticleEffect::new(ParticleEffect::Heal, transform.translation));
+    }
+}
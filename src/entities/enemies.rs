@@ -2,28 +2,32 @@ use std::time::Duration;
 
 use bevy::{math::Vec3Swizzles, prelude::*};
 use bevy_rapier2d::prelude::*;
-use parse_display::Display;
+use enum_iterator::Sequence;
+use parse_display::{Display, FromStr};
+use serde::Deserialize;
 
 use crate::{
+    animation::{render_animation, AnimatedEntity, Animation, AnimationClips},
     collisions::{ENEMY_ATTACK_COLLISION_GROUP, ENEMY_MOVE_COLLISION_GROUP},
-    constants::{ANIMATION_DURATION, ATTACK_DURATION, SPEED, TILE_SIZE},
+    constants::{ATTACK_DURATION, BOSS_CHARGE_FACTOR, SPEED, TILE_SIZE},
+    depth::YSort,
     entities::{
         from_position,
-        render_animation,
-        AnimatedEntity,
-        Animation,
         AttackTimer,
         Attackable,
+        Boss,
+        BossPhase,
         HitTimer,
         Player,
         Status,
     },
     frames::TexturePack,
+    weather::Weather,
     GameAssetType,
     GameAssets,
 };
 use crate::constants::HIT_DURATION;
-use crate::events::{DamagePlayer, EmitParticleEffect};
+use crate::events::{AnimationEvent, DamagePlayer, EmitParticleEffect};
 use crate::particles::ParticleEffect;
 
 #[derive(Debug, Clone, Copy, Display, PartialEq)]
@@ -48,13 +52,25 @@ impl AttackType {
     }
 }
 
-#[derive(Debug, Clone, Copy, Display, PartialEq, Component, Reflect)]
+#[derive(Debug, Clone, Copy, Display, FromStr, PartialEq, Component, Reflect, Deserialize, Sequence)]
 #[display(style = "snake_case")]
+#[serde(rename_all = "snake_case")]
 pub enum EnemyType {
     Squid,
     Raccoon,
     Spirit,
     Bamboo,
+    Boss,
+}
+
+impl EnemyType {
+    /// The monster whose sprites are used to render this enemy type
+    pub fn sprite(&self) -> EnemyType {
+        match self {
+            EnemyType::Boss => EnemyType::Raccoon,
+            ty => *ty,
+        }
+    }
 }
 
 impl From<i32> for EnemyType {
@@ -64,6 +80,7 @@ impl From<i32> for EnemyType {
             391 => EnemyType::Spirit,
             392 => EnemyType::Raccoon,
             393 => EnemyType::Squid,
+            396 => EnemyType::Boss,
             _ => unreachable!(),
         }
     }
@@ -97,6 +114,12 @@ impl Enemy {
         self.status == Status::Attack
     }
 
+    /// Whether the enemy is chasing or attacking the player. Chasing counts too on purpose, so that the combat music
+    /// starts as soon as an enemy notices the player, and doesn't stop in between two attacks.
+    pub fn is_in_combat(&self) -> bool {
+        matches!(self.status, Status::Attack | Status::Move(_))
+    }
+
     pub fn can_move(&self) -> bool {
         self.can_move
     }
@@ -111,6 +134,7 @@ impl Enemy {
             EnemyType::Raccoon => AttackType::Claw,
             EnemyType::Spirit => AttackType::Thunder,
             EnemyType::Bamboo => AttackType::Leaf,
+            EnemyType::Boss => AttackType::Claw,
         }
     }
 
@@ -120,6 +144,7 @@ impl Enemy {
             EnemyType::Raccoon => 300,
             EnemyType::Spirit => 100,
             EnemyType::Bamboo => 70,
+            EnemyType::Boss => 1000,
         }
     }
 
@@ -129,6 +154,7 @@ impl Enemy {
             EnemyType::Raccoon => 250,
             EnemyType::Spirit => 110,
             EnemyType::Bamboo => 120,
+            EnemyType::Boss => 1500,
         }
     }
 
@@ -138,6 +164,7 @@ impl Enemy {
             EnemyType::Raccoon => 40,
             EnemyType::Spirit => 8,
             EnemyType::Bamboo => 6,
+            EnemyType::Boss => 30,
         }
     }
 
@@ -147,6 +174,7 @@ impl Enemy {
             EnemyType::Raccoon => 2.0,
             EnemyType::Spirit => 4.0,
             EnemyType::Bamboo => 3.0,
+            EnemyType::Boss => 2.5,
         }
     }
 
@@ -156,6 +184,7 @@ impl Enemy {
             EnemyType::Raccoon => 3.0,
             EnemyType::Spirit => 3.0,
             EnemyType::Bamboo => 3.0,
+            EnemyType::Boss => 1.0,
         }
     }
 
@@ -165,6 +194,7 @@ impl Enemy {
             EnemyType::Raccoon => 120.0,
             EnemyType::Spirit => 60.0,
             EnemyType::Bamboo => 50.0,
+            EnemyType::Boss => 140.0,
         }
     }
 
@@ -174,67 +204,52 @@ impl Enemy {
             EnemyType::Raccoon => 400.0,
             EnemyType::Spirit => 350.0,
             EnemyType::Bamboo => 300.0,
+            EnemyType::Boss => 600.0,
         }
     }
-}
-
-impl AnimatedEntity for Enemy {
-    fn asset_name(&self) -> String {
-        "monsters".to_string()
-    }
 
-    fn texture_name(&self) -> String {
-        format!("monsters/{}/{}/00.png", self.ty, self.status)
+    pub fn scale(&self) -> f32 {
+        match self.ty {
+            EnemyType::Boss => 1.5,
+            _ => 1.0,
+        }
     }
+}
 
-    fn num_frames(&self) -> usize {
-        let frames = match self.ty {
-            EnemyType::Bamboo => [1, 4, 4],
-            EnemyType::Raccoon => [4, 6, 5],
-            EnemyType::Spirit => [1, 4, 4],
-            EnemyType::Squid => [1, 4, 4],
-        };
-
-        match self.status {
-            Status::Attack => frames[0],
-            Status::CastSpell => 0,
-            Status::Idle => frames[1],
-            Status::Move(_) => frames[2],
-        }
+impl AnimatedEntity for Enemy {
+    fn clip_name(&self) -> String {
+        format!("monsters/{}/{}", self.ty.sprite(), self.status)
     }
 }
 
 pub fn spawn_enemy(
     commands: &mut Commands,
     window: &Window,
-    asset_server: &Res<AssetServer>,
     assets: &Res<GameAssets>,
     atlases: &Res<Assets<TextureAtlas>>,
     textures: &Res<Assets<TexturePack>>,
-    cell: i32,
+    ty: EnemyType,
     x: f32,
     y: f32,
-) {
-    let ty = EnemyType::from(cell);
-
-    let name = format!("monsters/{ty}/idle/00.png");
-    let handle = asset_server.load("textures/monsters.json");
-
-    let pack = textures.get(&handle).expect("Texture pack must exist");
+) -> Entity {
+    let name = format!("monsters/{}/idle/00.png", ty.sprite());
+    let pack = assets.pack(GameAssetType::Monsters, textures);
     let index = pack.index_of(&name);
 
     let atlas_handle = assets.get(GameAssetType::Monsters);
     let atlas = atlases.get(atlas_handle).unwrap();
     let rect = atlas.textures[index];
-    let offset = (rect.height() - TILE_SIZE) / 2.0;
-    let y = y - offset;
-    let collider_height = (rect.height() - TILE_SIZE / 4.0) / 2.0;
-    let collider_width = (rect.width() - TILE_SIZE / 4.0) / 2.0;
 
     let enemy = Enemy::new(ty);
     let health = enemy.health();
+    let scale = enemy.scale();
 
-    let transform = Transform::from_translation(from_position(x, y, window));
+    let offset = (rect.height() * scale - TILE_SIZE) / 2.0;
+    let y = y - offset;
+    let collider_height = (rect.height() - TILE_SIZE / 4.0) / 2.0;
+    let collider_width = (rect.width() - TILE_SIZE / 4.0) / 2.0;
+
+    let transform = Transform::from_translation(from_position(x, y, window)).with_scale(Vec3::splat(scale));
 
     commands
         .spawn((
@@ -248,7 +263,8 @@ pub fn spawn_enemy(
             GravityScale(0.0),
             LockedAxes::ROTATION_LOCKED,
             Velocity::zero(),
-            Animation::new(ANIMATION_DURATION),
+            Animation::default(),
+            YSort::default(),
             Attackable::new(health),
             enemy,
         ))
@@ -267,42 +283,71 @@ pub fn spawn_enemy(
                 ENEMY_MOVE_COLLISION_GROUP.clone(),
                 ColliderDebugColor(Color::DARK_GRAY),
             ));
-        });
+        })
+        .id()
 }
 
+type MovingEnemyQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Enemy,
+        &'static Transform,
+        &'static mut Velocity,
+        &'static mut Animation,
+        Option<&'static Boss>,
+    ),
+>;
+
 pub fn move_enemy(
     mut commands: Commands,
     mut player_q: Query<(Entity, &mut Player, &Transform)>,
-    mut enemy_q: Query<(Entity, &mut Enemy, &Transform, &mut Velocity, &mut Animation)>,
-    mut damage_player_writer: EventWriter<DamagePlayer>,
+    mut enemy_q: MovingEnemyQuery,
     mut particle_effect_writer: EventWriter<EmitParticleEffect>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    weather: Res<Weather>,
 ) {
     let (player_e, mut player, player_transform) = player_q.single_mut();
 
-    for (entity, mut enemy, transform, mut velocity, mut animation) in enemy_q.iter_mut() {
+    for (entity, mut enemy, transform, mut velocity, mut animation, boss) in enemy_q.iter_mut() {
         if enemy.is_attacking() || !enemy.can_move() {
             continue;
         }
 
+        // Bosses wait for the player to enter their arena
+        if boss.is_some_and(|boss| !boss.active) {
+            continue;
+        }
+
+        let (speed, attack_radius, attack_cooldown) = match boss {
+            Some(boss) => (
+                enemy.speed() * boss.phase.speed_factor(),
+                boss.phase.attack_radius(),
+                boss.phase.attack_cooldown(),
+            ),
+            None => (enemy.speed(), enemy.attack_radius(), enemy.attack_cooldown()),
+        };
+
         let diff = player_transform.translation - transform.translation;
         let distance = diff.length();
-        let direction = diff.xy().normalize_or_zero() * enemy.speed() * SPEED;
+        let direction = diff.xy().normalize_or_zero() * speed * SPEED;
 
         let mut status = Status::Idle;
 
-        if distance < enemy.attack_radius() && enemy.can_attack {
+        if distance < attack_radius && enemy.can_attack {
             velocity.linvel = Vec2::ZERO;
             enemy.can_attack = false;
             status = Status::Attack;
 
+            // While charging, the boss lunges at the player instead of standing still
+            if boss.is_some_and(|boss| boss.phase == BossPhase::Charge) {
+                velocity.linvel = direction * BOSS_CHARGE_FACTOR;
+            }
+
             commands
                 .entity(entity)
-                .insert(AttackTimer(Timer::new(enemy.attack_cooldown(), TimerMode::Once)));
-
-            damage_player_writer.send(DamagePlayer(entity));
-        } else if distance < enemy.notice_radius() {
+                .insert(AttackTimer(Timer::new(attack_cooldown, TimerMode::Once)));
+        } else if distance < enemy.notice_radius() * weather.notice_factor() {
             velocity.linvel = direction.into();
             status = Status::Move(direction);
         } else {
@@ -319,12 +364,37 @@ pub fn move_enemy(
 
 pub fn render_enemy(
     time: Res<Time>,
-    mut query: Query<(&Enemy, &mut Animation, &mut TextureAtlasSprite)>,
-    asset_server: Res<AssetServer>,
-    textures: Res<Assets<TexturePack>>,
+    mut query: Query<(Entity, &Enemy, &mut Animation, &mut TextureAtlasSprite)>,
+    clips: Res<AnimationClips>,
+    mut animation_event_writer: EventWriter<AnimationEvent>,
 ) {
-    for (enemy, mut animation, mut sprite) in query.iter_mut() {
-        render_animation(enemy, &mut animation, &mut sprite, &time, &asset_server, &textures);
+    for (entity, enemy, mut animation, mut sprite) in query.iter_mut() {
+        render_animation(
+            entity,
+            enemy,
+            &mut animation,
+            &mut sprite,
+            &time,
+            &clips,
+            &mut animation_event_writer,
+        );
+    }
+}
+
+/// Damages the player when the attack of an enemy lands, i.e. on the `hit` frame of its clip
+pub fn land_enemy_attack(
+    enemy_q: Query<&Enemy>,
+    mut animation_event_reader: EventReader<AnimationEvent>,
+    mut damage_player_writer: EventWriter<DamagePlayer>,
+) {
+    for event in animation_event_reader.iter() {
+        if event.name != "hit" {
+            continue;
+        }
+
+        if enemy_q.get(event.entity).is_ok_and(|enemy| enemy.is_attacking()) {
+            damage_player_writer.send(DamagePlayer(event.entity));
+        }
     }
 }
 
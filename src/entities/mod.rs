@@ -1,14 +1,15 @@
-use std::{f32::consts::FRAC_PI_4, time::Duration};
+use std::f32::consts::FRAC_PI_4;
 
 use bevy::prelude::*;
-use bevy_rapier2d::prelude::Velocity;
 use parse_display::Display;
 
+pub use boss::*;
 pub use enemies::*;
 pub use player::*;
 
-use crate::frames::TexturePack;
+use crate::constants::{TILE_SIZE, Y_SORT_DEPTH};
 
+mod boss;
 mod enemies;
 mod player;
 
@@ -24,6 +25,13 @@ pub struct EnergyRecoveryTimer(pub Timer);
 #[derive(Component, Deref, DerefMut)]
 pub struct CastSpellTimer(pub Timer);
 
+#[derive(Component, Deref, DerefMut)]
+pub struct DodgeTimer(pub Timer);
+
+/// Prevents the player from dodging again right away
+#[derive(Component, Deref, DerefMut)]
+pub struct DodgeCooldown(pub Timer);
+
 #[derive(Component, Reflect)]
 pub struct Attackable {
     health: u32,
@@ -34,6 +42,10 @@ impl Attackable {
         Self { health }
     }
 
+    pub fn health(&self) -> u32 {
+        self.health
+    }
+
     pub fn hit(&mut self, damage: u32) -> u32 {
         if self.health > damage {
             self.health -= damage;
@@ -53,6 +65,16 @@ pub enum Status {
     CastSpell,
     #[display("move")]
     Move(Vec2),
+    /// Rolling quickly in a direction, without taking any damage
+    #[display("dodge")]
+    Dodge(Vec2),
+}
+
+impl Status {
+    /// Standing still or walking, free to start an action
+    pub fn is_free(&self) -> bool {
+        matches!(self, Status::Idle | Status::Move(_))
+    }
 }
 
 #[derive(Debug, Clone, Copy, Display, PartialEq, Reflect)]
@@ -91,84 +113,18 @@ impl Direction {
     }
 }
 
-#[derive(Component)]
-pub struct Animation {
-    current_frame: usize,
-    num_frames: usize,
-    timer: Timer,
+/// Converts a world translation back to map coordinates, the inverse of `from_position`
+pub fn to_position(translation: Vec3, window: &Window) -> Vec2 {
+    Vec2::new(translation.x + window.width() / 2., window.height() / 2. - translation.y)
 }
 
-impl Animation {
-    pub fn new(duration: Duration) -> Self {
-        let mut timer = Timer::new(duration, TimerMode::Repeating);
-        timer.pause();
-
-        Self {
-            current_frame: 0,
-            num_frames: 0,
-            timer,
-        }
+/// The map cell, as (row, column), at the given map coordinates
+pub fn to_cell(position: Vec2) -> Option<(usize, usize)> {
+    if position.x < 0. || position.y < 0. {
+        return None;
     }
 
-    pub fn next_frame(&mut self, delta: Duration) -> usize {
-        self.timer.tick(delta);
-
-        if self.timer.just_finished() {
-            self.current_frame = (self.current_frame + 1) % self.num_frames;
-        }
-
-        self.current_frame
-    }
-
-    pub fn is_paused(&self) -> bool {
-        self.timer.paused()
-    }
-
-    pub fn play(&mut self, num_frames: usize) {
-        self.num_frames = num_frames;
-        self.current_frame = 0;
-        self.timer.reset();
-        self.timer.unpause()
-    }
-
-    pub fn stop(&mut self) {
-        self.timer.pause()
-    }
-}
-
-pub trait AnimatedEntity {
-    fn asset_name(&self) -> String;
-    fn texture_name(&self) -> String;
-    fn num_frames(&self) -> usize;
-}
-
-pub fn update_depth(mut query: Query<(&mut Transform, Ref<Velocity>)>) {
-    for (mut transform, previous) in query.iter_mut() {
-        if previous.is_changed() {
-            transform.translation.z = -transform.translation.y + 1000.0;
-        }
-    }
-}
-
-pub fn render_animation(
-    game_entity: &dyn AnimatedEntity,
-    animation: &mut Animation,
-    sprite: &mut TextureAtlasSprite,
-    time: &Res<Time>,
-    asset_server: &Res<AssetServer>,
-    textures: &Res<Assets<TexturePack>>,
-) {
-    let handle = asset_server.load(format!("textures/{}.json", game_entity.asset_name()));
-    let pack = textures.get(&handle).expect("Texture pack must exist");
-    let index = pack.index_of(&game_entity.texture_name());
-
-    if animation.is_paused() {
-        animation.play(game_entity.num_frames());
-        sprite.index = index;
-    } else {
-        let frame = animation.next_frame(time.delta());
-        sprite.index = index + frame;
-    }
+    Some(((position.y / TILE_SIZE) as usize, (position.x / TILE_SIZE) as usize))
 }
 
 pub fn from_position(x: f32, y: f32, window: &Window) -> Vec3 {
@@ -179,6 +135,6 @@ pub fn from_position(x: f32, y: f32, window: &Window) -> Vec3 {
     Vec3::new(
         convert(x, window.width()),
         -convert(y, window.height()),
-        convert(y, window.height()) + 1000.0,
+        convert(y, window.height()) + Y_SORT_DEPTH,
     )
 }
@@ -6,21 +6,22 @@ use enum_iterator::{all, Sequence};
 use parse_display::Display;
 
 use crate::{
+    animation::{render_animation, AnimatedEntity, Animation, AnimationClips},
     collisions::PLAYER_MOVE_COLLISION_GROUP,
-    constants::{ANIMATION_DURATION, ATTACK_DURATION, ENERGY_RECOVERY_DURATION, STARTING_XP, TILE_SIZE},
+    constants::{ATTACK_DURATION, DODGE_COOLDOWN, ENERGY_RECOVERY_DURATION, HIT_INPUT_LOCK, STARTING_XP, TILE_SIZE},
+    depth::YSort,
     entities::{
         from_position,
-        render_animation,
-        AnimatedEntity,
-        Animation,
         AttackTimer,
         CastSpellTimer,
         Direction,
+        DodgeCooldown,
+        DodgeTimer,
         EnergyRecoveryTimer,
         HitTimer,
         Status,
     },
-    frames::TexturePack,
+    events::AnimationEvent,
     stats::Stat,
     weapon::PlayerWeapon,
     GameAssetType,
@@ -136,11 +137,23 @@ impl PlayerStats {
         self.get_mut(ty).set(val)
     }
 
+    pub fn set_limit(&mut self, ty: PlayerStat, val: u32) {
+        self.get_mut(ty).set_limit(val);
+    }
+
     pub fn upgrade(&mut self, ty: PlayerStat) {
         self.get_mut(ty).upgrade();
     }
 }
 
+/// Ignores any damage
+#[derive(Component)]
+pub struct GodMode;
+
+/// Walks through walls and objects
+#[derive(Component)]
+pub struct Noclip;
+
 #[derive(Component, Reflect)]
 pub struct Player {
     pub xp: u32,
@@ -185,6 +198,10 @@ impl Player {
         matches!(self.status, Status::CastSpell)
     }
 
+    pub fn is_dodging(&self) -> bool {
+        matches!(self.status, Status::Dodge(_))
+    }
+
     pub fn hit(&mut self, damage: u32) {
         let health = self.stats.value(PlayerStat::Health);
         let health = if health > damage {
@@ -197,6 +214,10 @@ impl Player {
     }
 
     pub fn cast_spell(&mut self, cost: u32) -> bool {
+        self.spend_energy(cost)
+    }
+
+    pub fn spend_energy(&mut self, cost: u32) -> bool {
         let energy = self.stats.value(PlayerStat::Energy);
 
         if energy >= cost {
@@ -212,6 +233,17 @@ impl Player {
         self.stats.set(PlayerStat::Health, health + strength);
     }
 
+    pub fn gain(&mut self, stat: PlayerStat, amount: u32) {
+        let value = self.stats.value(stat) + amount;
+        self.stats.set(stat, value.min(self.stats.limit(stat)));
+    }
+
+    pub fn restore(&mut self) {
+        for stat in [PlayerStat::Health, PlayerStat::Energy] {
+            self.stats.set(stat, self.stats.limit(stat));
+        }
+    }
+
     pub fn recover_energy(&mut self, amount: u32) {
         let energy = self.stats.value(PlayerStat::Energy);
         self.stats.set(PlayerStat::Energy, energy + amount);
@@ -243,29 +275,15 @@ impl Player {
 }
 
 impl AnimatedEntity for Player {
-    fn asset_name(&self) -> String {
-        "player".to_string()
-    }
-
-    fn texture_name(&self) -> String {
-        let postfix = if self.is_moving() { "_0" } else { "" };
-
+    fn clip_name(&self) -> String {
         let status_name = match self.status {
             Status::Idle => "idle",
             Status::Move(_) => "move",
+            Status::Dodge(_) => "dodge",
             _ => "attack",
         };
 
-        format!("player/{status_name}/{}{postfix}.png", self.direction)
-    }
-
-    fn num_frames(&self) -> usize {
-        match self.status {
-            Status::Attack => 1,
-            Status::CastSpell => 1,
-            Status::Idle => 1,
-            Status::Move(_) => 4,
-        }
+        format!("player/{status_name}/{}", self.direction)
     }
 }
 
@@ -283,7 +301,8 @@ pub fn spawn_player(commands: &mut Commands, window: &Window, assets: &Res<GameA
             GravityScale(0.0),
             LockedAxes::ROTATION_LOCKED,
             Velocity::zero(),
-            Animation::new(ANIMATION_DURATION),
+            Animation::default(),
+            YSort::default(),
             EnergyRecoveryTimer(Timer::new(ENERGY_RECOVERY_DURATION, TimerMode::Repeating)),
         ))
         .with_children(|parent| {
@@ -298,14 +317,22 @@ pub fn spawn_player(commands: &mut Commands, window: &Window, assets: &Res<GameA
 }
 
 pub fn render_player(
-    mut query: Query<(&Player, &mut Animation, &mut TextureAtlasSprite)>,
+    mut query: Query<(Entity, &Player, &mut Animation, &mut TextureAtlasSprite)>,
     time: Res<Time>,
-    asset_server: Res<AssetServer>,
-    textures: Res<Assets<TexturePack>>,
+    clips: Res<AnimationClips>,
+    mut animation_event_writer: EventWriter<AnimationEvent>,
 ) {
-    let (player, mut animation, mut sprite) = query.single_mut();
-
-    render_animation(player, &mut animation, &mut sprite, &time, &asset_server, &textures);
+    let (entity, player, mut animation, mut sprite) = query.single_mut();
+
+    render_animation(
+        entity,
+        player,
+        &mut animation,
+        &mut sprite,
+        &time,
+        &clips,
+        &mut animation_event_writer,
+    );
 }
 
 pub fn end_player_attack(
@@ -344,20 +371,65 @@ pub fn end_player_spell_cast(
     }
 }
 
+pub fn end_player_dodge(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut player_q: Query<(Entity, &mut Player, &mut Velocity, &mut DodgeTimer)>,
+) {
+    if let Ok((entity, mut player, mut velocity, mut timer)) = player_q.get_single_mut() {
+        timer.0.tick(time.delta());
+
+        if timer.0.finished() {
+            player.status = Status::Idle;
+            velocity.linvel = Vec2::ZERO;
+            commands
+                .entity(entity)
+                .remove::<DodgeTimer>()
+                .insert(DodgeCooldown(Timer::new(DODGE_COOLDOWN, TimerMode::Once)));
+        }
+    }
+}
+
+pub fn cool_down_dodge(mut commands: Commands, time: Res<Time>, mut player_q: Query<(Entity, &mut DodgeCooldown)>) {
+    if let Ok((entity, mut cooldown)) = player_q.get_single_mut() {
+        cooldown.0.tick(time.delta());
+
+        if cooldown.0.finished() {
+            commands.entity(entity).remove::<DodgeCooldown>();
+        }
+    }
+}
+
 pub fn handle_player_hit(
     mut commands: Commands,
     time: Res<Time>,
-    mut player_q: Query<(Entity, &mut HitTimer, &mut TextureAtlasSprite), With<Player>>,
+    mut player_q: Query<(
+        Entity,
+        &mut Player,
+        &mut Velocity,
+        &mut HitTimer,
+        &mut TextureAtlasSprite,
+    )>,
 ) {
-    let Ok((entity, mut timer, mut sprite)) = player_q.get_single_mut() else {
+    let Ok((entity, mut player, mut velocity, mut timer, mut sprite)) = player_q.get_single_mut() else {
         return;
     };
 
     let delta = time.delta();
     let elapsed = time.elapsed();
 
+    let locked = timer.0.elapsed() < HIT_INPUT_LOCK;
     timer.0.tick(delta);
 
+    // The knockback is over, the input moves the player again
+    if locked && timer.0.elapsed() >= HIT_INPUT_LOCK {
+        velocity.linvel = Vec2::ZERO;
+
+        if player.is_moving() {
+            player.status = Status::Idle;
+        }
+    }
+
     if timer.0.finished() {
         sprite.color.set_a(1.0);
         commands.entity(entity).remove::<HitTimer>();
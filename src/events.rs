@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::particles::ParticleEffect;
+use crate::{map::LayerType, particles::ParticleEffect, tiles::Destructible};
 
 pub struct SwitchMagic;
 
@@ -45,6 +45,30 @@ pub struct DamageAttackable(pub Entity);
 
 pub struct DamagePlayer(pub Entity);
 
+/// Sent when an entity shows a frame of its animation clip with an event, e.g. `hit`
+pub struct AnimationEvent {
+    pub entity: Entity,
+    pub name: String,
+}
+
+pub struct BossDefeated(pub Entity);
+
+/// Despawns the level and spawns it again, from the `WorldMap` in memory or reading it from disk again
+pub struct RestartLevel {
+    pub reload_map: bool,
+}
+
+/// Spawns again the tiles of a layer, e.g. after it changed on disk
+pub struct RespawnLayer(pub LayerType);
+
+/// Adds trauma, between 0 and 1, to the camera
+pub struct ShakeCamera(pub f32);
+
+pub struct DestructibleBroken {
+    pub tile: Destructible,
+    pub pos: Vec3,
+}
+
 impl EmitParticleEffect {
     pub fn new(ty: ParticleEffect, pos: Vec3) -> Self {
         Self {
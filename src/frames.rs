@@ -1,23 +1,127 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use bevy::reflect::TypeUuid;
 use serde::Deserialize;
 
+/// The frames of a texture pack, with the lookups by name and by clip built once, when loaded
 #[derive(Debug, Deserialize, TypeUuid)]
+#[serde(from = "TexturePackData")]
 #[uuid = "1c363550-1333-4889-8145-2633d881c243"]
 pub struct TexturePack {
     pub frames: BTreeMap<String, TextureFrame>,
+    /// Name of each frame, by index in the atlas
+    names: Vec<String>,
+    /// Index of each frame in the atlas, by name
+    indices: HashMap<String, usize>,
+    /// Indices of the frames of each clip, in order
+    clips: BTreeMap<String, Vec<usize>>,
+}
+
+/// A texture pack as stored on disk
+#[derive(Deserialize)]
+struct TexturePackData {
+    frames: BTreeMap<String, TextureFrame>,
+}
+
+impl From<TexturePackData> for TexturePack {
+    fn from(data: TexturePackData) -> Self {
+        let indices = data
+            .frames
+            .keys()
+            .enumerate()
+            .map(|(id, key)| (key.clone(), id))
+            .collect();
+        let names = data.frames.keys().cloned().collect();
+        let clips = group_clips(data.frames.keys());
+
+        Self {
+            frames: data.frames,
+            names,
+            indices,
+            clips,
+        }
+    }
+}
+
+/// Groups the frames in clips, by folder and by name without the frame number, e.g. `player/move/down` for
+/// `player/move/down_0.png` to `player/move/down_3.png`, or `monsters/bamboo/idle` for `monsters/bamboo/idle/00.png`
+fn group_clips<'a>(keys: impl Iterator<Item = &'a String>) -> BTreeMap<String, Vec<usize>> {
+    let mut clips = BTreeMap::<String, Vec<(usize, usize)>>::new();
+
+    for (id, key) in keys.enumerate() {
+        let path = key.strip_suffix(".png").unwrap_or(key);
+        let (folder, name) = path.rsplit_once('/').unwrap_or(("", path));
+
+        let (clip, number) = if let Ok(number) = name.parse() {
+            (folder.to_string(), number)
+        } else if let Some((prefix, number)) = name
+            .rsplit_once('_')
+            .and_then(|(prefix, number)| number.parse().ok().map(|number| (prefix, number)))
+        {
+            (format!("{folder}/{prefix}"), number)
+        } else {
+            (path.to_string(), 0)
+        };
+
+        clips.entry(clip).or_default().push((number, id));
+    }
+
+    clips
+        .into_iter()
+        .map(|(clip, mut frames)| {
+            frames.sort();
+            (clip, frames.into_iter().map(|(_, id)| id).collect())
+        })
+        .collect()
 }
 
 impl TexturePack {
     pub fn index_of(&self, frame: &str) -> usize {
-        for (id, key) in self.frames.keys().enumerate() {
-            if key == frame {
-                return id;
-            }
-        }
+        self.get_index(frame)
+            .unwrap_or_else(|| unreachable!("Cannot find index of {frame}"))
+    }
+
+    pub fn get_index(&self, frame: &str) -> Option<usize> {
+        self.indices.get(frame).copied()
+    }
 
-        unreachable!("Cannot find index of {frame}")
+    pub fn frame(&self, frame: &str) -> &TextureFrame {
+        &self.frames[frame]
+    }
+
+    /// The name and the frame at the given index in the atlas
+    pub fn frame_at(&self, index: usize) -> Option<(&str, &TextureFrame)> {
+        let name = self.names.get(index)?;
+
+        Some((name, &self.frames[name]))
+    }
+
+    /// Indices of the numbered frames in a folder, in order (e.g. `particles/flame/00.png`, but not `full.png`)
+    pub fn frame_indices(&self, folder: &str) -> Vec<usize> {
+        self.clip(folder).map(|frames| frames.to_vec()).unwrap_or_default()
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&[usize]> {
+        self.clips.get(name).map(|frames| frames.as_slice())
+    }
+
+    pub fn clips(&self) -> &BTreeMap<String, Vec<usize>> {
+        &self.clips
+    }
+
+    /// Folders named `name`, optionally followed by a number (e.g. `leaf1` to `leaf6` for `leaf`)
+    pub fn variants(&self, parent: &str, name: &str) -> Vec<String> {
+        self.clips
+            .keys()
+            .filter_map(|clip| clip.strip_prefix(parent)?.strip_prefix('/'))
+            .filter(|folder| {
+                !folder.contains('/')
+                    && folder
+                        .strip_prefix(name)
+                        .is_some_and(|suffix| suffix.chars().all(|c| c.is_ascii_digit()))
+            })
+            .map(|folder| folder.to_string())
+            .collect()
     }
 }
 
@@ -59,7 +163,44 @@ mod tests {
 
         assert_eq!(tile_set.frames.len(), 1);
         let tile = &tile_set.frames["0.png"];
-        assert_eq!(tile.frame.x, 0);
+        assert_eq!(tile.frame.x, 0.);
+        assert_eq!(tile_set.frame_at(0).map(|(name, _)| name), Some("0.png"));
+        assert!(tile_set.frame_at(1).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn group_frames_by_folder() -> Result<()> {
+        let frame = r#"{ "frame": { "x": 0, "y": 0, "w": 64, "h": 64 } }"#;
+        let names = [
+            "particles/flame/00.png",
+            "particles/flame/01.png",
+            "particles/flame/full.png",
+            "particles/leaf1/00.png",
+            "particles/leaf2/00.png",
+            "particles/leaf_attack/00.png",
+            "player/move/down_1.png",
+            "player/move/down_0.png",
+            "player/idle/down.png",
+        ];
+        let frames = names
+            .iter()
+            .map(|name| format!(r#""{name}": {frame}"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let pack = serde_json::from_str::<TexturePack>(&format!(r#"{{ "frames": {{ {frames} }} }}"#))?;
+
+        assert_eq!(pack.frame_indices("particles/flame"), vec![0, 1]);
+        assert_eq!(pack.frame_indices("particles/smoke"), Vec::<usize>::new());
+        assert_eq!(pack.variants("particles", "leaf"), vec!["leaf1", "leaf2"]);
+        assert_eq!(pack.variants("particles", "flame"), vec!["flame"]);
+
+        let clips = pack.clips();
+        assert_eq!(clips["particles/flame"], vec![0, 1]);
+        assert_eq!(clips["particles/flame/full"], vec![2]);
+        assert_eq!(clips["player/move/down"], vec![7, 8]);
+        assert_eq!(clips["player/idle/down"], vec![6]);
 
         Ok(())
     }
@@ -1,24 +1,88 @@
-use std::fs::File;
+use std::io::Read;
 
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+};
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "6a0e3c1f-1d2b-4f7e-9c51-2b8f0d7a4e93"]
 pub struct Layer {
     pub data: Vec<Vec<i32>>,
 }
 
 impl Layer {
-    pub fn load(path: &str) -> Self {
-        let file = File::open(path).expect(format!("File {} does not exist", path).as_str());
+    pub fn read(reader: impl Read) -> Result<Self> {
         let mut data = Vec::new();
-        let mut rdr = csv::ReaderBuilder::new().has_headers(false).from_reader(file);
+        let mut rdr = csv::ReaderBuilder::new().has_headers(false).from_reader(reader);
         for result in rdr.records() {
-            let record = result.unwrap();
-            data.push(
-                record
-                    .iter()
-                    .map(|r| r.parse::<i32>().expect("Record is not an int"))
-                    .collect::<Vec<_>>(),
-            );
+            let record = result?;
+            data.push(record.iter().map(|r| r.parse::<i32>()).collect::<Result<Vec<_>, _>>()?);
+        }
+
+        Ok(Self { data })
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut wtr = csv::WriterBuilder::new().has_headers(false).from_path(path)?;
+        for row in &self.data {
+            wtr.write_record(row.iter().map(|cell| cell.to_string()))?;
         }
+        wtr.flush()?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<i32> {
+        self.data.get(row).and_then(|cells| cells.get(col)).copied()
+    }
+
+    /// Changes a cell, returning its previous value, or `None` when it's outside the layer
+    pub fn set(&mut self, row: usize, col: usize, cell: i32) -> Option<i32> {
+        let current = self.data.get_mut(row)?.get_mut(col)?;
+
+        Some(std::mem::replace(current, cell))
+    }
+}
+
+/// Loads the `csv` layers of the maps, to be reloaded when they change on disk
+#[derive(Default)]
+pub struct LayerLoader;
+
+impl AssetLoader for LayerLoader {
+    fn load<'a>(&'a self, bytes: &'a [u8], load_context: &'a mut LoadContext) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            load_context.set_default_asset(LoadedAsset::new(Layer::read(bytes)?));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["csv"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join("zeldust_layer.csv");
+        let path = path.to_str().unwrap();
+        let mut layer = Layer {
+            data: vec![vec![-1, 395, -1], vec![8, -1, 394]],
+        };
+
+        assert_eq!(layer.set(1, 1, 20), Some(-1));
+        assert_eq!(layer.set(2, 0, 20), None);
+        layer.save(path).expect("Layer saved");
 
-        Self { data }
+        let file = std::fs::File::open(path).expect("Layer exists");
+        assert_eq!(
+            Layer::read(file).expect("Valid layer").data,
+            vec![vec![-1, 395, -1], vec![8, 20, 394]]
+        );
     }
 }
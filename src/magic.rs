@@ -2,12 +2,13 @@ use bevy::prelude::*;
 use parse_display::Display;
 use rand::Rng;
 
-use bevy_kira_audio::{Audio, AudioControl};
 use crate::{
+    audio::PlaySound,
     constants::TILE_SIZE,
     entities::{EnergyRecoveryTimer, Player},
-    events::{EmitParticleEffect, SwitchMagic},
+    events::{EmitParticleEffect, ShakeCamera, SwitchMagic},
     particles::ParticleEffect,
+    rng::{GameRng, RngStream},
 };
 
 #[derive(Component)]
@@ -62,8 +63,9 @@ pub fn cast_spell(
     current_magic: Res<Magic>,
     mut player_q: Query<(&mut Player, &Transform)>,
     mut particle_effect_writer: EventWriter<EmitParticleEffect>,
-    asset_server: Res<AssetServer>,
-    audio: Res<Audio>,
+    mut play_sound_writer: EventWriter<PlaySound>,
+    mut shake_camera_writer: EventWriter<ShakeCamera>,
+    mut rng: ResMut<GameRng>,
 ) {
     let (mut player, transform) = player_q.single_mut();
 
@@ -84,9 +86,9 @@ pub fn cast_spell(
             },
             Magic::Flame => {
                 let offset = player.direction.as_vec2().extend(0.);
+                let rng = rng.stream(RngStream::Combat);
                 for i in 1..6 {
                     let mut offset = offset * i as f32 * TILE_SIZE;
-                    let mut rng = rand::thread_rng();
                     offset.x += rng.gen_range(-TILE_SIZE / 3.0..TILE_SIZE / 3.0);
                     offset.y += rng.gen_range(-TILE_SIZE / 3.0..TILE_SIZE / 3.0);
                     particle_effect_writer.send(EmitParticleEffect::new(
@@ -94,10 +96,11 @@ pub fn cast_spell(
                         transform.translation + offset,
                     ));
                 }
+                shake_camera_writer.send(ShakeCamera(0.25));
             },
         }
 
-        audio.play(asset_server.load(current_magic.sound()));
+        play_sound_writer.send(PlaySound::sfx(current_magic.sound()));
     }
 }
 
@@ -1,54 +1,97 @@
 use std::collections::HashMap;
 
 use bevy::{
-    asset::LoadState,
+    asset::{Asset, FileAssetIo, LoadState},
+    ecs::system::SystemParam,
     prelude::*,
-    window::{PrimaryWindow, WindowResolution},
+    window::{PrimaryWindow, WindowMode, WindowResolution},
 };
 use bevy_common_assets::json::JsonAssetPlugin;
 use bevy_inspector_egui::{
     bevy_egui::{EguiContext, EguiPlugin},
     bevy_inspector, egui,
 };
-use bevy_kira_audio::{Audio, AudioControl, AudioPlugin};
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
+use clap::Parser;
 use enum_iterator::{all, Sequence};
 use parse_display::Display;
 
-use crate::events::DamagePlayer;
+use crate::events::{
+    AnimationEvent,
+    BossDefeated,
+    DamagePlayer,
+    DestructibleBroken,
+    RespawnLayer,
+    RestartLevel,
+    ShakeCamera,
+};
 use crate::{
-    constants::{SCREEN_HEIGHT, SCREEN_WIDTH},
-    debug::{DEBUG_PHYSICS, DEBUG_WORLD},
-    entities::{Attackable, Enemy, Player},
+    animation::{reload_animation_clips, AnimationClips, ClipDefinitions},
+    audio::{GameAudioPlugin, PlaySound},
+    camera::CameraController,
+    cli::Cli,
+    console::{ConsoleCommand, ConsoleOutput, ConsolePlugin},
+    constants::{ASSETS_DIR, DAY_CLEAR_COLOR, SETTINGS_PATH},
+    daytime::{DayCycle, TimeOfDay},
+    depth::DepthAnchors,
+    editor::EditorPlugin,
+    entities::{Attackable, Boss, Enemy, Player},
     events::{
         DamageAttackable, EmitParticleEffect, KillAttackable, MagicCollision, SwitchMagic, SwitchWeapon,
         WeaponCollision,
     },
     frames::TexturePack,
+    layer::LayerLoader,
     magic::Magic,
-    map::{LayerType, WorldMap},
-    screens::{GameMode, GameScreenPlugin, UpgradeScreenPlugin},
+    map::{LayerType, LevelSelection, Levels, MapAssets, MapManifest, WorldMap},
+    music::MusicDefinitions,
+    particles::ParticleDefinitions,
+    replay::ReplayPlugin,
+    rng::{log_seed, GameRng},
+    screens::{GameMode, GameScreenPlugin, GameViewPlugin, UpgradeScreenPlugin},
+    settings::Settings,
+    spawner::Spawners,
+    tiles::TileMetadata,
     ui::{MagicItemBox, WeaponItemBox},
     weapon::Weapon,
+    weather::{Weather, WeatherRegions},
     widgets::WidgetsPlugin,
 };
 
+mod animation;
+mod audio;
 mod camera;
+mod cli;
 mod collisions;
+mod console;
 mod constants;
+mod daytime;
 mod debug;
+mod depth;
+mod editor;
 mod entities;
 mod events;
 mod frames;
 mod layer;
 mod magic;
 mod map;
+mod minimap;
+mod music;
+mod packer;
 mod particles;
+mod replay;
+mod rng;
 mod screens;
+mod settings;
+#[cfg(test)]
+mod simulation;
+mod spawner;
 mod stats;
+mod tiles;
 mod ui;
 mod weapon;
+mod weather;
 mod widgets;
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash, States)]
@@ -73,6 +116,10 @@ pub struct Map;
 #[derive(Component)]
 pub struct StaticCollider;
 
+/// Survives the reload of the map
+#[derive(Component)]
+pub struct Persistent;
+
 #[derive(Debug, Clone, Copy, Display, PartialEq, Eq, Hash, Sequence)]
 #[display(style = "snake_case")]
 pub enum GameAssetType {
@@ -94,15 +141,43 @@ impl From<&LayerType> for GameAssetType {
     }
 }
 
+impl GameAssetType {
+    pub fn pack_path(&self) -> String {
+        format!("textures/{self}.json")
+    }
+
+    pub fn image_path(&self) -> String {
+        format!("textures/{self}.png")
+    }
+}
+
 #[derive(Resource)]
 pub struct GameAssets {
     handles: HashMap<GameAssetType, Handle<TextureAtlas>>,
+    packs: HashMap<GameAssetType, Handle<TexturePack>>,
 }
 
 impl GameAssets {
     pub fn get(&self, ty: GameAssetType) -> &Handle<TextureAtlas> {
         &self.handles[&ty]
     }
+
+    pub fn pack_handle(&self, ty: GameAssetType) -> &Handle<TexturePack> {
+        &self.packs[&ty]
+    }
+
+    /// The type of the given atlas, if it's one of the texture packs
+    pub fn asset_type(&self, atlas: &Handle<TextureAtlas>) -> Option<GameAssetType> {
+        self.handles
+            .iter()
+            .find(|(_, handle)| *handle == atlas)
+            .map(|(ty, _)| *ty)
+    }
+
+    /// The frames of the atlas of the given type
+    pub fn pack<'a>(&self, ty: GameAssetType, textures: &'a Assets<TexturePack>) -> &'a TexturePack {
+        textures.get(self.pack_handle(ty)).expect("Texture pack must exist")
+    }
 }
 
 #[derive(Default, Resource)]
@@ -110,102 +185,276 @@ pub struct LoadingAssets {
     handles: HashMap<HandleUntyped, bool>,
 }
 
+/// The game logic, without any window, rendering or audio, so that it can also run headless
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            .add_plugin(JsonAssetPlugin::<TexturePack>::new(&["json"]))
+            .add_plugin(JsonAssetPlugin::<MapManifest>::new(&["map.json"]))
+            .add_plugin(JsonAssetPlugin::<TileMetadata>::new(&["tiles.json"]))
+            .add_plugin(JsonAssetPlugin::<Spawners>::new(&["spawners.json"]))
+            .add_plugin(JsonAssetPlugin::<Levels>::new(&["levels.json"]))
+            .add_plugin(JsonAssetPlugin::<WeatherRegions>::new(&["weather.json"]))
+            .add_plugin(JsonAssetPlugin::<MusicDefinitions>::new(&["music.json"]))
+            .add_plugin(JsonAssetPlugin::<ParticleDefinitions>::new(&["particles.json"]))
+            .add_plugin(JsonAssetPlugin::<ClipDefinitions>::new(&["clips.json"]))
+            .add_plugin(JsonAssetPlugin::<DepthAnchors>::new(&["anchors.json"]))
+            .add_plugin(JsonAssetPlugin::<DayCycle>::new(&["cycle.json"]))
+            .add_asset::<layer::Layer>()
+            .init_asset_loader::<LayerLoader>()
+            .register_type::<Attackable>()
+            .register_type::<Weapon>()
+            .register_type::<Magic>()
+            .register_type::<Enemy>()
+            .register_type::<Boss>()
+            .register_type::<Player>()
+            .register_type::<GameMode>()
+            .add_event::<SwitchMagic>()
+            .add_event::<SwitchWeapon>()
+            .add_event::<DamagePlayer>()
+            .add_event::<AnimationEvent>()
+            .add_event::<MagicCollision>()
+            .add_event::<WeaponCollision>()
+            .add_event::<EmitParticleEffect>()
+            .add_event::<KillAttackable>()
+            .add_event::<DamageAttackable>()
+            .add_event::<BossDefeated>()
+            .add_event::<DestructibleBroken>()
+            .add_event::<ShakeCamera>()
+            .add_event::<RestartLevel>()
+            .add_event::<RespawnLayer>()
+            .add_event::<PlaySound>()
+            .add_event::<ConsoleCommand>()
+            .add_event::<ConsoleOutput>()
+            .init_resource::<GameMode>()
+            .init_resource::<LoadingAssets>()
+            .init_resource::<Weapon>()
+            .init_resource::<Magic>()
+            .init_resource::<GameRng>()
+            .init_resource::<Settings>()
+            .init_resource::<LevelSelection>()
+            .init_resource::<Weather>()
+            .add_startup_system(log_seed)
+            .add_state::<AppState>()
+            .add_systems(
+                (load_map_assets, load_assets, finish_loading).in_set(OnUpdate(AppState::LoadLevel)),
+            )
+            .add_systems((insert_map_data, prepare_assets, build_world_map).in_schedule(OnExit(AppState::LoadLevel)))
+            .add_systems((reload_atlases, reload_animation_clips).in_set(OnUpdate(AppState::RunLevel)))
+            .add_plugin(GameScreenPlugin);
+    }
+}
+
 fn main() {
+    let cli = Cli::parse();
+
+    if cli.pack_textures {
+        for (ty, num_frames) in packer::pack_textures().expect("Cannot pack the textures") {
+            println!("Packed {num_frames} {ty} frames");
+        }
+    }
+
+    let mut settings = Settings::load(&settings_file());
+    cli.apply(&mut settings);
+
+    if cli.save_settings {
+        settings.save(&settings_file());
+    }
+
     let mut app = App::new();
 
-    app.add_plugins(DefaultPlugins.set(WindowPlugin {
-        primary_window: Some(Window {
-            title: "Zeldust".to_string(),
-            resolution: WindowResolution::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32),
-            ..default()
-        }),
-        ..default()
-    }))
-    .add_plugin(AudioPlugin)
-    .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+    app.add_plugins(
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(Window {
+                    title: "Zeldust".to_string(),
+                    resolution: WindowResolution::new(settings.window.width as f32, settings.window.height as f32),
+                    mode: if settings.window.fullscreen {
+                        WindowMode::BorderlessFullscreen
+                    } else {
+                        WindowMode::Windowed
+                    },
+                    ..default()
+                }),
+                ..default()
+            })
+            // Maps and texture packs are reloaded when they change on disk
+            .set(AssetPlugin {
+                watch_for_changes: true,
+                ..default()
+            }),
+    )
+    .add_plugin(GameAudioPlugin)
     .add_plugin(ShapePlugin)
-    .add_plugin(JsonAssetPlugin::<TexturePack>::new(&["json"]))
     .add_plugin(WidgetsPlugin)
-    .register_type::<Attackable>()
-    .register_type::<Weapon>()
-    .register_type::<Magic>()
-    .register_type::<Enemy>()
+    .add_plugin(SimulationPlugin)
+    .register_type::<CameraController>()
     .register_type::<MagicItemBox>()
-    .register_type::<Player>()
-    .register_type::<GameMode>()
     .register_type::<WeaponItemBox>()
-    .register_type::<GameMode>()
-    .add_event::<SwitchMagic>()
-    .add_event::<SwitchWeapon>()
-    .add_event::<DamagePlayer>()
-    .add_event::<MagicCollision>()
-    .add_event::<WeaponCollision>()
-    .add_event::<EmitParticleEffect>()
-    .add_event::<KillAttackable>()
-    .add_event::<DamageAttackable>()
-    .insert_resource(ClearColor(Color::hex("70deee").unwrap()))
-    .init_resource::<GameMode>()
-    .init_resource::<LoadingAssets>()
-    .init_resource::<Weapon>()
-    .init_resource::<Magic>()
-    .add_state::<AppState>()
-    .add_system(load_map.in_schedule(OnEnter(AppState::LoadLevel)))
-    .add_systems((load_ground, load_assets, finish_loading).in_set(OnUpdate(AppState::LoadLevel)))
-    .add_system(prepare_assets.in_schedule(OnExit(AppState::LoadLevel)))
-    .add_plugin(GameScreenPlugin)
-    .add_plugin(UpgradeScreenPlugin);
-
-    if DEBUG_WORLD {
+    .insert_resource(ClearColor(DAY_CLEAR_COLOR))
+    .insert_resource(settings.clone())
+    .insert_resource(cli.level_selection())
+    .add_plugin(GameViewPlugin)
+    .add_plugin(UpgradeScreenPlugin)
+    .add_plugin(ConsolePlugin)
+    .add_plugin(EditorPlugin);
+
+    if let Some(seed) = cli.seed {
+        app.insert_resource(GameRng::new(seed));
+    }
+
+    // A replay brings its own seed
+    if let Some(mode) = cli.replay_mode() {
+        app.add_plugin(ReplayPlugin { mode });
+    }
+
+    if settings.debug.world {
         // app.add_plugin(WorldInspectorPlugin::default());
         app.add_plugin(EguiPlugin)
             .add_plugin(bevy_inspector_egui::DefaultInspectorConfigPlugin)
             .add_system(inspector_ui);
     }
 
-    if DEBUG_PHYSICS {
+    if settings.debug.physics {
         app.add_plugin(RapierDebugRenderPlugin::default());
     }
 
     app.run();
 }
 
-fn load_map(mut commands: Commands, asset_server: Res<AssetServer>, audio: Res<Audio>) {
-    // commands.insert_resource(WorldMap::debug_grass());
+/// The path of an asset on disk, relative to the executable or to the crate when launched by cargo
+pub fn asset_file(path: &str) -> String {
+    FileAssetIo::get_base_path()
+        .join(ASSETS_DIR)
+        .join(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The path of the settings file, next to the assets folder rather than in the working directory
+pub fn settings_file() -> String {
+    FileAssetIo::get_base_path()
+        .join(SETTINGS_PATH)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The data files of the maps, loaded by the asset server
+#[derive(SystemParam)]
+struct MapData<'w> {
+    tiles: Res<'w, Assets<TileMetadata>>,
+    spawners: Res<'w, Assets<Spawners>>,
+    levels: Res<'w, Assets<Levels>>,
+    weather: Res<'w, Assets<WeatherRegions>>,
+    music: Res<'w, Assets<MusicDefinitions>>,
+    particles: Res<'w, Assets<ParticleDefinitions>>,
+    anchors: Res<'w, Assets<DepthAnchors>>,
+    cycles: Res<'w, Assets<DayCycle>>,
+}
+
+fn loaded<T: Asset + Clone>(assets: &Assets<T>, handle: &Handle<T>) -> T {
+    assets.get(handle).expect("Map data not loaded").clone()
+}
+
+/// Makes the data files of the map available as resources, once they're all loaded
+fn insert_map_data(
+    mut commands: Commands,
+    selection: Res<LevelSelection>,
+    map_assets: Res<MapAssets>,
+    data: MapData,
+    spawners: Option<Res<Spawners>>,
+    time_of_day: Option<Res<TimeOfDay>>,
+) {
+    // Spawners provided beforehand (e.g. none in the simulation tests) are kept
+    if spawners.is_none() {
+        commands.insert_resource(loaded(&data.spawners, &map_assets.spawners));
+    }
+    commands.insert_resource(loaded(&data.tiles, &map_assets.tiles));
     commands.insert_resource(
-        WorldMap::new()
-            .load_layer(LayerType::Blocks, "assets/map/map_FloorBlocks.csv")
-            .load_layer(LayerType::Grass, "assets/map/map_Grass.csv")
-            .load_layer(LayerType::Objects, "assets/map/map_Objects.csv")
-            .load_layer(LayerType::Entities, "assets/map/map_Entities.csv"),
+        map_assets
+            .weather
+            .as_ref()
+            .map(|handle| loaded(&data.weather, handle))
+            .unwrap_or_default(),
     );
+    commands.insert_resource(loaded(&data.music, &map_assets.music));
+    commands.insert_resource(loaded(&data.particles, &map_assets.particles));
+    commands.insert_resource(loaded(&data.anchors, &map_assets.anchors));
 
-    audio
-        .play(asset_server.load("audio/main.ogg"))
-        .looped()
-        .with_playback_rate(1.2)
-        .with_volume(0.4);
-}
+    let cycle = loaded(&data.cycles, &map_assets.day_cycle);
+    if time_of_day.is_none() {
+        commands.insert_resource(TimeOfDay::new(cycle.start_hour));
+    }
+    commands.insert_resource(cycle);
 
-fn load_ground(asset_server: Res<AssetServer>, mut assets: ResMut<LoadingAssets>) {
-    load(&asset_server, &mut assets, "map/ground.png");
+    let levels = map_assets.levels.as_ref().map(|handle| loaded(&data.levels, handle));
+    if let Some(level) = selection.start(levels.as_ref()) {
+        commands.insert_resource(level);
+    }
 }
 
 fn load_assets(asset_server: Res<AssetServer>, mut assets: ResMut<LoadingAssets>) {
     for ty in all::<GameAssetType>() {
-        for asset in vec!["json", "png"] {
-            let path = format!("textures/{ty}.{asset}");
+        for path in [ty.pack_path(), ty.image_path()] {
             load(&asset_server, &mut assets, path);
         }
     }
 }
 
+/// Loads the manifest of the map, then the files it lists
+fn load_map_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut assets: ResMut<LoadingAssets>,
+    selection: Res<LevelSelection>,
+    settings: Res<Settings>,
+    manifests: Res<Assets<MapManifest>>,
+    (world_map, map_assets): (Option<Res<WorldMap>>, Option<Res<MapAssets>>),
+) {
+    if let Some(map_assets) = map_assets {
+        for handle in map_assets.handles() {
+            track(&asset_server, &mut assets, handle);
+        }
+        return;
+    }
+
+    // A map provided beforehand (e.g. by the simulation tests) takes precedence over the layers
+    let layers = match world_map {
+        Some(_) => vec![],
+        None => settings.debug.layers.clone(),
+    };
+
+    match MapAssets::load(&asset_server, &selection, &manifests, &layers) {
+        Some(map_assets) => {
+            for handle in map_assets.handles() {
+                track(&asset_server, &mut assets, handle);
+            }
+            commands.insert_resource(map_assets);
+        },
+        None => {
+            let manifest = MapAssets::manifest(&asset_server, &selection);
+            track(&asset_server, &mut assets, manifest.clone_untyped());
+        },
+    }
+}
+
 fn load(asset_server: &Res<AssetServer>, assets: &mut LoadingAssets, path: impl Into<String>) {
     let handle = asset_server.load_untyped(path.into());
 
+    track(asset_server, assets, handle);
+}
+
+fn track(asset_server: &Res<AssetServer>, assets: &mut LoadingAssets, handle: HandleUntyped) {
     match asset_server.get_load_state(handle.clone()) {
         LoadState::Loaded => {
             assets.handles.insert(handle, true);
         },
+        LoadState::Failed => {
+            let path = asset_server.get_handle_path(&handle);
+            panic!("Cannot load {:?}", path.map(|path| path.path().to_path_buf()));
+        },
         _ => {
             assets.handles.insert(handle, false);
         },
@@ -234,13 +483,13 @@ fn finish_loading(
 fn prepare_assets(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut images: ResMut<Assets<Image>>,
+    map_assets: Res<MapAssets>,
+    images: Res<Assets<Image>>,
     tiles_data: Res<Assets<TexturePack>>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    clip_definitions: Res<Assets<ClipDefinitions>>,
 ) {
-    let handle = asset_server.load("map/ground.png");
-
-    let image = images.get(&handle).expect("Ground image does not exist");
+    let image = images.get(&map_assets.ground).expect("Ground image does not exist");
 
     let width = image.texture_descriptor.size.width as f32;
     let height = image.texture_descriptor.size.height as f32;
@@ -249,33 +498,39 @@ fn prepare_assets(
 
     commands.insert_resource(size);
 
+    let packs = all::<GameAssetType>()
+        .map(|ty| (ty, asset_server.load(ty.pack_path())))
+        .collect::<HashMap<_, _>>();
+
     let handles = all::<GameAssetType>()
         .map(|ty| {
-            (
-                ty,
-                build_texture_atlas(ty, &asset_server, &mut images, &mut texture_atlases, &tiles_data),
-            )
+            let atlas = build_texture_atlas(&packs[&ty], &asset_server, ty, &images, &tiles_data);
+
+            (ty, texture_atlases.add(atlas))
         })
         .collect::<HashMap<_, _>>();
 
-    let assets = GameAssets { handles };
+    let assets = GameAssets { handles, packs };
 
+    // Also kept as a resource, to rebuild the clips when the texture packs change
+    let clip_definitions = clip_definitions
+        .get(&map_assets.clips)
+        .expect("Animation clips not loaded");
+    commands.insert_resource(AnimationClips::new(&assets, &tiles_data, clip_definitions));
+    commands.insert_resource(clip_definitions.clone());
     commands.insert_resource(assets);
 }
 
 fn build_texture_atlas(
+    pack: &Handle<TexturePack>,
+    asset_server: &AssetServer,
     ty: GameAssetType,
-    asset_server: &Res<AssetServer>,
-    images: &mut ResMut<Assets<Image>>,
-    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
-    textures: &Res<Assets<TexturePack>>,
-) -> Handle<TextureAtlas> {
-    let path = format!("textures/{ty}");
-
-    let handle = asset_server.load(format!("{path}.json"));
-    let pack = textures.get(&handle).expect("Texture pack not loaded");
+    images: &Assets<Image>,
+    textures: &Assets<TexturePack>,
+) -> TextureAtlas {
+    let pack = textures.get(pack).expect("Texture pack not loaded");
 
-    let handle = asset_server.load(format!("{path}.png"));
+    let handle = asset_server.load(ty.image_path());
     let image = images.get(&handle).expect("Image not loaded");
 
     let mut atlas = TextureAtlas::new_empty(handle, image.size());
@@ -286,7 +541,64 @@ fn build_texture_atlas(
         atlas.add_texture(rect);
     }
 
-    texture_atlases.add(atlas)
+    atlas
+}
+
+fn build_world_map(
+    mut commands: Commands,
+    world_map: Option<Res<WorldMap>>,
+    map_assets: Res<MapAssets>,
+    layers: Res<Assets<layer::Layer>>,
+) {
+    if world_map.is_some() {
+        return;
+    }
+
+    let world_map = WorldMap::from_assets(&map_assets, &layers).expect("Map layers not loaded");
+
+    commands.insert_resource(world_map);
+}
+
+/// Rebuilds the atlases whose texture pack or image changed on disk, in place for the sprites using them
+fn reload_atlases(
+    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
+    images: Res<Assets<Image>>,
+    textures: Res<Assets<TexturePack>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut texture_events: EventReader<AssetEvent<TexturePack>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+) {
+    let modified = texture_events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.id()),
+            _ => None,
+        })
+        .chain(image_events.iter().filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.id()),
+            _ => None,
+        }))
+        .collect::<Vec<_>>();
+
+    if modified.is_empty() {
+        return;
+    }
+
+    for ty in all::<GameAssetType>() {
+        let json = assets.pack_handle(ty).id();
+        let png = texture_atlases.get(assets.get(ty)).map(|atlas| atlas.texture.id());
+
+        if !modified.contains(&json) && !png.is_some_and(|png| modified.contains(&png)) {
+            continue;
+        }
+
+        let atlas = build_texture_atlas(assets.pack_handle(ty), &asset_server, ty, &images, &textures);
+        if let Some(current) = texture_atlases.get_mut(assets.get(ty)) {
+            *current = atlas;
+            info!("Reloaded {ty} texture atlas");
+        }
+    }
 }
 
 fn inspector_ui(world: &mut World) {
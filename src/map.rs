@@ -1,10 +1,25 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, reflect::TypeUuid};
 use bevy_rapier2d::parry::utils::hashmap::HashMap;
+use clap::ValueEnum;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::{debug::VALID_LAYERS, layer::Layer};
+use crate::{
+    animation::ClipDefinitions,
+    asset_file,
+    constants::MAP_MANIFEST,
+    daytime::DayCycle,
+    depth::DepthAnchors,
+    layer::Layer,
+    music::MusicDefinitions,
+    particles::ParticleDefinitions,
+    spawner::Spawners,
+    tiles::TileMetadata,
+    weather::WeatherRegions,
+};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
 pub enum LayerType {
     Blocks,
     Entities,
@@ -13,18 +28,197 @@ pub enum LayerType {
 }
 
 impl LayerType {
-    pub fn to_index(&self, index: usize) -> usize {
+    pub fn to_index(&self, index: usize, rng: &mut impl Rng) -> usize {
         match self {
-            LayerType::Grass => {
-                let mut rng = rand::thread_rng();
-                rng.gen_range(0..3)
-            },
+            LayerType::Grass => rng.gen_range(0..3),
             _ => index,
         }
     }
+}
+
+/// The files of a map, relative to its directory
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "0f5d9b8e-4a61-4c3b-a7e2-91c4d6b3f820"]
+pub struct MapManifest {
+    /// The image of the whole map, under every tile
+    pub ground: String,
+    pub layers: HashMap<LayerType, String>,
+    pub tiles: String,
+    pub spawners: String,
+    /// Where the player starts when skipping to a level
+    pub levels: Option<String>,
+    /// Maps without any weather don't need it
+    pub weather: Option<String>,
+    pub shared: SharedFiles,
+}
+
+/// Files which can be shared by several maps, relative to the assets folder
+#[derive(Debug, Deserialize)]
+pub struct SharedFiles {
+    pub music: String,
+    pub particles: String,
+    pub clips: String,
+    pub anchors: String,
+    pub day_cycle: String,
+}
+
+/// The map to play, and where to start in it
+#[derive(Debug, Clone, Resource)]
+pub struct LevelSelection {
+    /// Directory with the layers, the spawners and the tiles of the map, in the assets folder
+    pub map: String,
+    /// One of the levels listed in the manifest of the map
+    pub level: Option<String>,
+}
+
+impl Default for LevelSelection {
+    fn default() -> Self {
+        Self {
+            map: "map".to_string(),
+            level: None,
+        }
+    }
+}
+
+impl LevelSelection {
+    /// The path of a file of the map on disk, wherever the game is launched from
+    pub fn path(&self, file_name: &str) -> String {
+        asset_file(&self.asset_path(file_name))
+    }
+
+    /// The path of a file of the map, for the asset server
+    pub fn asset_path(&self, file_name: &str) -> String {
+        format!("{}/{file_name}", self.map)
+    }
+
+    /// Where the player starts, when skipping to a level
+    pub fn start(&self, levels: Option<&Levels>) -> Option<Level> {
+        let name = self.level.as_ref()?;
+        let Some(levels) = levels else {
+            panic!("Unknown level {name}, the map has no levels");
+        };
+
+        match levels.0.get(name) {
+            Some(level) => Some(*level),
+            None => panic!(
+                "Unknown level {name}, expected one of {:?}",
+                levels.0.keys().collect::<Vec<_>>()
+            ),
+        }
+    }
+}
+
+/// The starting points of a map, by name
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "0fb5c84d-389c-465a-9205-2086d64f01d8"]
+pub struct Levels(pub HashMap<String, Level>);
+
+/// A starting point of the map, in tiles
+#[derive(Debug, Clone, Copy, Resource, Deserialize)]
+pub struct Level {
+    pub x: usize,
+    pub y: usize,
+}
 
-    pub fn is_attackable(&self) -> bool {
-        matches!(*self, LayerType::Grass)
+/// An area of the map, in tiles
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Zone {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Zone {
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        (self.x..self.x + self.width).contains(&col) && (self.y..self.y + self.height).contains(&row)
+    }
+}
+
+/// The files of the map loaded by the asset server, followed when they change on disk
+#[derive(Resource)]
+pub struct MapAssets {
+    pub manifest: Handle<MapManifest>,
+    pub ground: Handle<Image>,
+    pub layers: HashMap<LayerType, Handle<Layer>>,
+    pub tiles: Handle<TileMetadata>,
+    pub spawners: Handle<Spawners>,
+    pub levels: Option<Handle<Levels>>,
+    pub weather: Option<Handle<WeatherRegions>>,
+    pub music: Handle<MusicDefinitions>,
+    pub particles: Handle<ParticleDefinitions>,
+    pub clips: Handle<ClipDefinitions>,
+    pub anchors: Handle<DepthAnchors>,
+    pub day_cycle: Handle<DayCycle>,
+}
+
+impl MapAssets {
+    pub fn manifest(asset_server: &AssetServer, selection: &LevelSelection) -> Handle<MapManifest> {
+        asset_server.load(selection.asset_path(MAP_MANIFEST))
+    }
+
+    /// Loads the files listed in the manifest, limited to the given layers
+    pub fn load(
+        asset_server: &AssetServer,
+        selection: &LevelSelection,
+        manifests: &Assets<MapManifest>,
+        layers: &[LayerType],
+    ) -> Option<Self> {
+        let manifest = Self::manifest(asset_server, selection);
+        let files = manifests.get(&manifest)?;
+
+        let layers = files
+            .layers
+            .iter()
+            .filter(|(ty, _)| layers.contains(ty))
+            .map(|(ty, file_name)| (*ty, asset_server.load(selection.asset_path(file_name))))
+            .collect();
+
+        let path = |file_name: &String| selection.asset_path(file_name);
+        let shared = &files.shared;
+
+        Some(Self {
+            ground: asset_server.load(path(&files.ground)),
+            tiles: asset_server.load(path(&files.tiles)),
+            spawners: asset_server.load(path(&files.spawners)),
+            levels: files
+                .levels
+                .as_ref()
+                .map(|file_name| asset_server.load(path(file_name))),
+            weather: files
+                .weather
+                .as_ref()
+                .map(|file_name| asset_server.load(path(file_name))),
+            music: asset_server.load(shared.music.as_str()),
+            particles: asset_server.load(shared.particles.as_str()),
+            clips: asset_server.load(shared.clips.as_str()),
+            anchors: asset_server.load(shared.anchors.as_str()),
+            day_cycle: asset_server.load(shared.day_cycle.as_str()),
+            manifest,
+            layers,
+        })
+    }
+
+    pub fn handles(&self) -> impl Iterator<Item = HandleUntyped> + '_ {
+        [
+            self.manifest.clone_untyped(),
+            self.ground.clone_untyped(),
+            self.tiles.clone_untyped(),
+            self.spawners.clone_untyped(),
+            self.music.clone_untyped(),
+            self.particles.clone_untyped(),
+            self.clips.clone_untyped(),
+            self.anchors.clone_untyped(),
+            self.day_cycle.clone_untyped(),
+        ]
+        .into_iter()
+        .chain(self.levels.iter().map(|handle| handle.clone_untyped()))
+        .chain(self.weather.iter().map(|handle| handle.clone_untyped()))
+        .chain(self.layers.values().map(|handle| handle.clone_untyped()))
+    }
+
+    pub fn layer_type(&self, handle: &Handle<Layer>) -> Option<LayerType> {
+        self.layers.iter().find(|(_, h)| *h == handle).map(|(ty, _)| *ty)
     }
 }
 
@@ -40,16 +234,37 @@ impl WorldMap {
         }
     }
 
-    pub fn load_layer(self, ty: LayerType, path: &str) -> Self {
-        let layer = Layer::load(path);
+    /// The layers loaded by the asset server, once they're all loaded
+    pub fn from_assets(map_assets: &MapAssets, layers: &Assets<Layer>) -> Option<Self> {
+        let layers = map_assets
+            .layers
+            .iter()
+            .map(|(ty, handle)| Some((*ty, layers.get(handle)?.clone())))
+            .collect::<Option<_>>()?;
+
+        Some(Self { layers })
+    }
+
+    /// Number of rows and columns covered by the layers
+    pub fn size(&self) -> (usize, usize) {
+        self.layers.values().fold((0, 0), |(rows, cols), layer| {
+            let layer_cols = layer.data.iter().map(|row| row.len()).max().unwrap_or(0);
 
-        let mut layers = self.layers;
+            (rows.max(layer.data.len()), cols.max(layer_cols))
+        })
+    }
 
-        if VALID_LAYERS.contains(&ty) {
-            layers.insert(ty, layer);
-        }
+    /// Changes a cell of a loaded layer, returning its previous value
+    pub fn set(&mut self, ty: LayerType, row: usize, col: usize, cell: i32) -> Option<i32> {
+        self.layers.get_mut(&ty)?.set(row, col, cell)
+    }
 
-        Self { layers }
+    /// Whether the cell is empty in all the given layers
+    pub fn is_free(&self, row: usize, col: usize, layers: &[LayerType]) -> bool {
+        layers
+            .iter()
+            .filter_map(|ty| self.layers.get(ty))
+            .all(|layer| layer.get(row, col) == Some(-1))
     }
 
     pub fn simple() -> Self {
@@ -153,7 +368,7 @@ impl WorldMap {
             vec![-1, -1, -1, -1, -1],
             vec![-1, -1, -1, -1, -1],
             vec![-1, -1, -1, -1, -1],
-            vec![-1, -1, 20, -1, -1],
+            vec![-1, -1, 8, -1, -1],
             vec![-1, -1, -1, -1, -1],
             vec![-1, -1, -1, -1, -1],
         ];
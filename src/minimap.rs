@@ -0,0 +1,313 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{
+    constants::{
+        BORDER_COLOR,
+        BORDER_WIDTH,
+        ENEMY_MARKER_COLOR,
+        MARGIN,
+        MARKER_SIZE,
+        MINIMAP_REVEAL_RADIUS,
+        MINIMAP_SIZE,
+        PLAYER_MARKER_COLOR,
+        TILE_SIZE,
+        WORLD_MAP_SIZE,
+    },
+    entities::{to_cell, to_position, Enemy, Player},
+    map::{LayerType, WorldMap},
+    screens::GameMode,
+};
+
+const UNEXPLORED: [u8; 4] = [0, 0, 0, 160];
+
+/// The explored areas of the map, drawn one pixel per tile
+#[derive(Resource)]
+pub struct Minimap {
+    image: Handle<Image>,
+    rows: usize,
+    cols: usize,
+    explored: Vec<bool>,
+}
+
+impl Minimap {
+    fn is_explored(&self, row: usize, col: usize) -> bool {
+        row < self.rows && col < self.cols && self.explored[row * self.cols + col]
+    }
+
+    /// Marks the cells around the given one as explored, returning whether anything changed
+    fn reveal(&mut self, row: usize, col: usize) -> bool {
+        let radius = MINIMAP_REVEAL_RADIUS;
+        let mut changed = false;
+
+        for r in row.saturating_sub(radius)..(row + radius + 1).min(self.rows) {
+            for c in col.saturating_sub(radius)..(col + radius + 1).min(self.cols) {
+                let (dr, dc) = (r.abs_diff(row), c.abs_diff(col));
+
+                if dr * dr + dc * dc > radius * radius {
+                    continue;
+                }
+
+                let explored = &mut self.explored[r * self.cols + c];
+                changed |= !*explored;
+                *explored = true;
+            }
+        }
+
+        changed
+    }
+
+    fn style(&self, game_mode: &GameMode, window: &Window) -> Style {
+        let ratio = self.cols as f32 / self.rows.max(1) as f32;
+
+        if *game_mode == GameMode::Map {
+            let height = window.height() * WORLD_MAP_SIZE;
+
+            Style {
+                size: Size::new(Val::Px(height * ratio), Val::Px(height)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px((window.width() - height * ratio) / 2.),
+                    top: Val::Px((window.height() - height) / 2.),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(BORDER_WIDTH)),
+                ..default()
+            }
+        } else {
+            Style {
+                size: Size::new(Val::Px(MINIMAP_SIZE * ratio), Val::Px(MINIMAP_SIZE)),
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Px(MARGIN),
+                    top: Val::Px(MARGIN),
+                    ..default()
+                },
+                border: UiRect::all(Val::Px(BORDER_WIDTH)),
+                ..default()
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct MinimapContainer;
+
+#[derive(Component)]
+pub struct MinimapMarker(Entity);
+
+fn cell_color(world_map: &WorldMap, row: usize, col: usize) -> [u8; 4] {
+    let is_set = |ty: LayerType| {
+        world_map
+            .layers
+            .get(&ty)
+            .and_then(|layer| layer.get(row, col))
+            .is_some_and(|cell| cell != -1)
+    };
+
+    if is_set(LayerType::Blocks) {
+        [60, 60, 70, 255]
+    } else if is_set(LayerType::Objects) {
+        [40, 90, 40, 255]
+    } else if is_set(LayerType::Grass) {
+        [90, 160, 70, 255]
+    } else {
+        [170, 200, 120, 255]
+    }
+}
+
+pub fn spawn_minimap(
+    mut commands: Commands,
+    world_map: Res<WorldMap>,
+    game_mode: Res<GameMode>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    let (rows, cols) = world_map.size();
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: cols as u32,
+            height: rows as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &UNEXPLORED,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    image.sampler_descriptor = ImageSampler::nearest();
+
+    let minimap = Minimap {
+        image: images.add(image),
+        rows,
+        cols,
+        explored: vec![false; rows * cols],
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: minimap.style(&game_mode, window),
+                background_color: BORDER_COLOR.into(),
+                ..default()
+            },
+            MinimapContainer,
+        ))
+        .with_children(|parent| {
+            parent.spawn(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    ..default()
+                },
+                image: minimap.image.clone().into(),
+                ..default()
+            });
+        });
+
+    commands.insert_resource(minimap);
+}
+
+pub fn toggle_world_map(mut keyboard_input: ResMut<Input<KeyCode>>, mut game_mode: ResMut<GameMode>) {
+    if !keyboard_input.just_pressed(KeyCode::Tab) {
+        return;
+    }
+
+    *game_mode = match *game_mode {
+        GameMode::Playing => GameMode::Map,
+        _ => GameMode::Playing,
+    };
+
+    keyboard_input.reset(KeyCode::Tab);
+}
+
+pub fn resize_minimap(
+    game_mode: Res<GameMode>,
+    minimap: Res<Minimap>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut container_q: Query<&mut Style, With<MinimapContainer>>,
+) {
+    if !game_mode.is_changed() {
+        return;
+    }
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    *container_q.single_mut() = minimap.style(&game_mode, window);
+}
+
+pub fn reveal_minimap(
+    world_map: Res<WorldMap>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<&Transform, With<Player>>,
+) {
+    let (Ok(window), Ok(transform)) = (window_q.get_single(), player_q.get_single()) else {
+        return;
+    };
+
+    let Some((row, col)) = to_cell(to_position(transform.translation, window)) else {
+        return;
+    };
+
+    if !minimap.reveal(row, col) {
+        return;
+    }
+
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+
+    for (index, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+        let (row, col) = (index / minimap.cols, index % minimap.cols);
+
+        if minimap.is_explored(row, col) {
+            pixel.copy_from_slice(&cell_color(&world_map, row, col));
+        }
+    }
+}
+
+type TrackedQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Transform, Option<&'static Player>), Or<(With<Player>, With<Enemy>)>>;
+
+pub fn update_minimap_markers(
+    mut commands: Commands,
+    minimap: Res<Minimap>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    container_q: Query<Entity, With<MinimapContainer>>,
+    entity_q: TrackedQuery,
+    mut marker_q: Query<(Entity, &MinimapMarker, &mut Style, &mut Visibility)>,
+) {
+    // The minimap may not have been spawned
+    let (Ok(window), Ok(container)) = (window_q.get_single(), container_q.get_single()) else {
+        return;
+    };
+
+    let mut tracked = Vec::new();
+
+    for (marker_e, marker, mut style, mut visibility) in marker_q.iter_mut() {
+        let Ok((_, transform, player)) = entity_q.get(marker.0) else {
+            commands.entity(marker_e).despawn_recursive();
+            continue;
+        };
+
+        tracked.push(marker.0);
+
+        let pos = to_position(transform.translation, window) / TILE_SIZE;
+        let visible =
+            player.is_some() || to_cell(pos * TILE_SIZE).is_some_and(|(row, col)| minimap.is_explored(row, col));
+
+        *visibility = if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        style.position.left = Val::Percent(pos.x / minimap.cols as f32 * 100.);
+        style.position.top = Val::Percent(pos.y / minimap.rows as f32 * 100.);
+    }
+
+    for (entity, _, player) in entity_q.iter() {
+        if tracked.contains(&entity) {
+            continue;
+        }
+
+        let (color, size) = match player {
+            Some(_) => (PLAYER_MARKER_COLOR, MARKER_SIZE * 1.5),
+            None => (ENEMY_MARKER_COLOR, MARKER_SIZE),
+        };
+
+        commands.entity(container).with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(size), Val::Px(size)),
+                        position_type: PositionType::Absolute,
+                        margin: UiRect {
+                            left: Val::Px(-size / 2.),
+                            top: Val::Px(-size / 2.),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    visibility: Visibility::Hidden,
+                    ..default()
+                },
+                MinimapMarker(entity),
+            ));
+        });
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid, window::PrimaryWindow};
+use bevy_kira_audio::{AudioChannel, AudioControl, AudioInstance, AudioTween, PlaybackState};
+use serde::Deserialize;
+
+use crate::{
+    audio::MusicChannel,
+    entities::{to_cell, to_position, Boss, Enemy, Player},
+    map::Zone,
+    screens::GameMode,
+};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TrackDefinition {
+    pub path: String,
+    #[serde(default = "default_playback_rate")]
+    pub playback_rate: f64,
+    #[serde(default = "default_volume")]
+    pub volume: f64,
+}
+
+fn default_playback_rate() -> f64 {
+    1.0
+}
+
+fn default_volume() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MusicRegion {
+    pub zone: Zone,
+    pub track: String,
+}
+
+/// Music tracks, and when to play them
+#[derive(Debug, Clone, Resource, Deserialize, TypeUuid)]
+#[uuid = "7a0aa880-7af7-42cf-ad69-651e93288ff3"]
+pub struct MusicDefinitions {
+    pub tracks: HashMap<String, TrackDefinition>,
+    pub default: String,
+    pub combat: Option<String>,
+    pub boss: Option<String>,
+    pub menu: Option<String>,
+    #[serde(default)]
+    pub regions: Vec<MusicRegion>,
+    /// Seconds to crossfade between two tracks
+    pub fade_duration: f32,
+}
+
+impl MusicDefinitions {
+    fn fade(&self) -> AudioTween {
+        AudioTween::linear(Duration::from_secs_f32(self.fade_duration))
+    }
+
+    fn region_track(&self, row: usize, col: usize) -> Option<&String> {
+        self.regions
+            .iter()
+            .find(|region| region.zone.contains(row, col))
+            .map(|region| &region.track)
+    }
+}
+
+#[derive(Default, Resource)]
+pub struct MusicController {
+    track: Option<String>,
+    instance: Option<Handle<AudioInstance>>,
+}
+
+/// Plays the tracks on the music channel
+#[derive(SystemParam)]
+pub struct MusicPlayback<'w> {
+    asset_server: Res<'w, AssetServer>,
+    channel: Res<'w, AudioChannel<MusicChannel>>,
+    controller: ResMut<'w, MusicController>,
+    instances: ResMut<'w, Assets<AudioInstance>>,
+}
+
+pub fn update_music(
+    definitions: Option<Res<MusicDefinitions>>,
+    game_mode: Res<GameMode>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<&Transform, With<Player>>,
+    enemy_q: Query<(&Enemy, Option<&Boss>)>,
+    playback: MusicPlayback,
+) {
+    let Some(definitions) = definitions else {
+        return;
+    };
+    let MusicPlayback {
+        asset_server,
+        channel,
+        mut controller,
+        mut instances,
+    } = playback;
+
+    let track = match *game_mode {
+        GameMode::Loading | GameMode::Map | GameMode::Console | GameMode::Editing => return,
+        GameMode::Paused => {
+            if let Some(instance) = controller.instance.as_ref().and_then(|h| instances.get_mut(h)) {
+                if matches!(instance.state(), PlaybackState::Playing { .. }) {
+                    instance.pause(definitions.fade());
+                }
+            }
+            return;
+        },
+        GameMode::Upgrading => definitions.menu.as_ref().unwrap_or(&definitions.default),
+        GameMode::Playing => if enemy_q.iter().any(|(_, boss)| boss.is_some_and(|boss| boss.active)) {
+            definitions.boss.as_ref()
+        } else if enemy_q.iter().any(|(enemy, _)| enemy.is_in_combat()) {
+            definitions.combat.as_ref()
+        } else {
+            None
+        }
+        .or_else(|| {
+            let window = window_q.get_single().ok()?;
+            let transform = player_q.get_single().ok()?;
+            let (row, col) = to_cell(to_position(transform.translation, window))?;
+
+            definitions.region_track(row, col)
+        })
+        .unwrap_or(&definitions.default),
+    };
+
+    if controller.track.as_ref() == Some(track) {
+        if let Some(instance) = controller.instance.as_ref().and_then(|h| instances.get_mut(h)) {
+            if matches!(
+                instance.state(),
+                PlaybackState::Paused { .. } | PlaybackState::Pausing { .. }
+            ) {
+                instance.resume(definitions.fade());
+            }
+        }
+        return;
+    }
+
+    let Some(definition) = definitions.tracks.get(track) else {
+        warn!("Unknown music track {track}");
+        controller.track = Some(track.clone());
+        return;
+    };
+
+    if let Some(instance) = controller.instance.take().and_then(|h| instances.get_mut(&h)) {
+        instance.stop(definitions.fade());
+    }
+
+    let instance = channel
+        .play(asset_server.load(definition.path.as_str()))
+        .looped()
+        .with_volume(definition.volume)
+        .with_playback_rate(definition.playback_rate)
+        .linear_fade_in(Duration::from_secs_f32(definitions.fade_duration))
+        .handle();
+
+    controller.track = Some(track.clone());
+    controller.instance = Some(instance);
+}
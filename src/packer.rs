@@ -0,0 +1,252 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use enum_iterator::all;
+use image::{imageops, RgbaImage};
+use serde::Serialize;
+
+use crate::{
+    asset_file,
+    constants::{ATLAS_MAX_WIDTH, ATLAS_PADDING},
+    GameAssetType,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Pivot {
+    x: f32,
+    y: f32,
+}
+
+/// A frame in the same format as the texture packs exported by free-tex-packer
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackedFrame {
+    pub frame: PackedRect,
+    rotated: bool,
+    trimmed: bool,
+    sprite_source_size: PackedRect,
+    source_size: Size,
+    pivot: Pivot,
+}
+
+impl PackedFrame {
+    fn new(frame: PackedRect) -> Self {
+        Self {
+            frame,
+            rotated: false,
+            trimmed: false,
+            sprite_source_size: PackedRect { x: 0, y: 0, ..frame },
+            source_size: Size { w: frame.w, h: frame.h },
+            pivot: Pivot { x: 0.5, y: 0.5 },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PackMeta {
+    app: &'static str,
+    image: String,
+    format: &'static str,
+    size: Size,
+    scale: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct PackData<'a> {
+    frames: &'a BTreeMap<String, PackedFrame>,
+    meta: PackMeta,
+}
+
+/// Images packed in a single texture, with the area of each of them
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    pub frames: BTreeMap<String, PackedFrame>,
+}
+
+impl PackedAtlas {
+    /// Packs the images in shelves, the tallest first, within a roughly square texture
+    pub fn pack(mut images: Vec<(String, RgbaImage)>) -> Self {
+        images.sort_by(|(a_name, a), (b_name, b)| b.height().cmp(&a.height()).then(a_name.cmp(b_name)));
+
+        let area = images
+            .iter()
+            .map(|(_, image)| (image.width() + ATLAS_PADDING) * (image.height() + ATLAS_PADDING))
+            .sum::<u32>();
+        let widest = images.iter().map(|(_, image)| image.width()).max().unwrap_or(0);
+        let max_width = ((area as f32).sqrt().ceil() as u32).clamp(widest, ATLAS_MAX_WIDTH.max(widest));
+
+        let (mut x, mut y, mut shelf_height, mut width) = (0, 0, 0, 0);
+        let mut placed = Vec::with_capacity(images.len());
+
+        for (name, image) in images {
+            if x > 0 && x + image.width() > max_width {
+                x = 0;
+                y += shelf_height + ATLAS_PADDING;
+                shelf_height = 0;
+            }
+
+            let rect = PackedRect {
+                x,
+                y,
+                w: image.width(),
+                h: image.height(),
+            };
+            x += image.width() + ATLAS_PADDING;
+            shelf_height = shelf_height.max(image.height());
+            width = width.max(rect.x + rect.w);
+
+            placed.push((name, image, rect));
+        }
+
+        let mut atlas = RgbaImage::new(width.max(1), (y + shelf_height).max(1));
+        let mut frames = BTreeMap::new();
+
+        for (name, image, rect) in placed {
+            imageops::replace(&mut atlas, &image, rect.x as i64, rect.y as i64);
+            frames.insert(name, PackedFrame::new(rect));
+        }
+
+        Self { image: atlas, frames }
+    }
+
+    /// Packs all the images in a folder and its subfolders, named after their path (e.g. `weapons/axe/full.png`)
+    pub fn pack_folder(images_dir: &Path, folder: &str) -> Result<Self> {
+        let mut images = vec![];
+
+        for path in find_images(&images_dir.join(folder))? {
+            let name = path
+                .strip_prefix(images_dir)?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            let image = image::open(&path)
+                .with_context(|| format!("Cannot read {}", path.display()))?
+                .to_rgba8();
+
+            images.push((name, image));
+        }
+
+        Ok(Self::pack(images))
+    }
+
+    /// Writes `name.png` and `name.json`, in the format of the texture packs
+    pub fn save(&self, textures_dir: &Path, name: &str) -> Result<()> {
+        let image = format!("{name}.png");
+        self.image.save(textures_dir.join(&image))?;
+
+        let data = PackData {
+            frames: &self.frames,
+            meta: PackMeta {
+                app: "zeldust",
+                image,
+                format: "RGBA8888",
+                size: Size {
+                    w: self.image.width(),
+                    h: self.image.height(),
+                },
+                scale: 1,
+            },
+        };
+        serde_json::to_writer_pretty(File::create(textures_dir.join(format!("{name}.json")))?, &data)?;
+
+        Ok(())
+    }
+}
+
+fn find_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut images = vec![];
+
+    for entry in fs::read_dir(dir).with_context(|| format!("Cannot read {}", dir.display()))? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            images.extend(find_images(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "png") {
+            images.push(path);
+        }
+    }
+
+    Ok(images)
+}
+
+/// Packs `assets/images/<type>` into the texture atlases in `assets/textures`, returning the number of frames of each
+pub fn pack_textures() -> Result<Vec<(GameAssetType, usize)>> {
+    let images_dir = PathBuf::from(asset_file("images"));
+    let textures_dir = PathBuf::from(asset_file("textures"));
+
+    all::<GameAssetType>()
+        .map(|ty| {
+            let atlas = PackedAtlas::pack_folder(&images_dir, &ty.to_string())?;
+            atlas.save(&textures_dir, &ty.to_string())?;
+
+            Ok((ty, atlas.frames.len()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::frames::TexturePack;
+
+    #[test]
+    fn pack_without_overlapping() -> Result<()> {
+        let images = [("a.png", 10, 20), ("b.png", 30, 10), ("c.png", 5, 5), ("d.png", 20, 20)]
+            .into_iter()
+            .map(|(name, w, h)| (name.to_string(), RgbaImage::from_pixel(w, h, Rgba([255, 0, 0, 255]))))
+            .collect();
+
+        let atlas = PackedAtlas::pack(images);
+        let rects = atlas.frames.values().map(|frame| frame.frame).collect::<Vec<_>>();
+
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.x + a.w <= atlas.image.width() && a.y + a.h <= atlas.image.height());
+
+            for b in &rects[i + 1..] {
+                let apart = a.x + a.w <= b.x || b.x + b.w <= a.x || a.y + a.h <= b.y || b.y + b.h <= a.y;
+                assert!(apart, "{a:?} overlaps {b:?}");
+            }
+        }
+
+        let d = atlas.frames["d.png"].frame;
+        assert_eq!(atlas.image.get_pixel(d.x, d.y), &Rgba([255, 0, 0, 255]));
+
+        // The same format as the texture packs
+        let json = serde_json::to_string(&PackData {
+            frames: &atlas.frames,
+            meta: PackMeta {
+                app: "test",
+                image: "test.png".to_string(),
+                format: "RGBA8888",
+                size: Size { w: 0, h: 0 },
+                scale: 1,
+            },
+        })?;
+        let pack = serde_json::from_str::<TexturePack>(&json)?;
+        assert_eq!(pack.index_of("c.png"), 2);
+        assert_eq!(pack.frame("b.png").frame.w, 30.);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,487 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    sprite::Mesh2dHandle,
+};
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    constants::{AMBIENT_PARTICLES_DEPTH, TILE_SIZE},
+    daytime::Emissive,
+    depth::above_map,
+    entities::Player,
+    events::WeaponCollision,
+    frames::TexturePack,
+    map::LayerType,
+    particles::ParticleDefinitions,
+    rng::{GameRng, RngStream},
+    tiles::{BreakParticle, Destructible, TileMetadata},
+    GameAssetType,
+    GameAssets,
+    MapSize,
+};
+
+/// How an emitter spawns its particles, and how they move and fade
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EmitterDefinition {
+    /// Name of the frame in the `particles` texture pack
+    pub frame: String,
+    /// Particles per second, while the emitter is active
+    pub rate: f32,
+    /// Particles emitted at once, when the emitter is spawned
+    pub burst: usize,
+    /// Seconds each particle lives, between the two values
+    pub lifetime: (f32, f32),
+    /// Pixels per second, between the two values
+    pub speed: (f32, f32),
+    /// Degrees, counterclockwise from the right
+    pub direction: f32,
+    /// Degrees around the direction the particles are emitted within
+    pub spread: f32,
+    /// Pixels per second squared
+    pub gravity: Vec2,
+    /// Half size, in pixels, of the area the particles are emitted from
+    pub area: Vec2,
+    /// Side, in pixels, of each particle
+    pub size: f32,
+    /// Length of each particle along its velocity, as a factor of its side (e.g. for rain streaks)
+    pub stretch: f32,
+    /// RGBA colors the particles go through, evenly over their life
+    pub colors: Vec<[f32; 4]>,
+}
+
+impl Default for EmitterDefinition {
+    fn default() -> Self {
+        Self {
+            frame: "particles/sparkle/00.png".to_string(),
+            rate: 0.,
+            burst: 0,
+            lifetime: (1., 1.),
+            speed: (0., 0.),
+            direction: 90.,
+            spread: 0.,
+            gravity: Vec2::ZERO,
+            area: Vec2::ZERO,
+            size: 8.,
+            stretch: 1.,
+            colors: vec![[1., 1., 1., 1.]],
+        }
+    }
+}
+
+impl EmitterDefinition {
+    /// The color of a particle at the given fraction of its life
+    fn color_at(&self, t: f32) -> Color {
+        let last = self.colors.len().saturating_sub(1);
+        let pos = t.clamp(0., 1.) * last as f32;
+        let index = (pos.floor() as usize).min(last.saturating_sub(1));
+
+        let color = match (self.colors.get(index), self.colors.get(index + 1)) {
+            (Some(from), Some(to)) => {
+                let t = pos - index as f32;
+                Vec4::from(*from).lerp(Vec4::from(*to), t)
+            },
+            (Some(color), None) => Vec4::from(*color),
+            _ => Vec4::ONE,
+        };
+
+        Color::rgba(color.x, color.y, color.z, color.w)
+    }
+}
+
+struct Particle {
+    pos: Vec2,
+    velocity: Vec2,
+    age: f32,
+    lifetime: f32,
+}
+
+/// Particles moved on the CPU and drawn all at once, without any physics
+#[derive(Component)]
+pub struct ParticleEmitter {
+    definition: EmitterDefinition,
+    particles: Vec<Particle>,
+    /// Particles due but not emitted yet, at the current rate
+    pending: f32,
+    /// Emits at its rate while active
+    pub active: bool,
+    /// Scales the rate, e.g. with how hard it rains
+    pub intensity: f32,
+    /// Despawned once its particles are gone
+    one_shot: bool,
+    /// Area of the frame in the atlas texture, once prepared
+    uv: Option<Rect>,
+}
+
+impl ParticleEmitter {
+    pub fn new(definition: EmitterDefinition) -> Self {
+        Self {
+            definition,
+            particles: vec![],
+            pending: 0.,
+            active: true,
+            intensity: 1.,
+            one_shot: false,
+            uv: None,
+        }
+    }
+
+    /// Emits its burst and then despawns
+    pub fn one_shot(definition: EmitterDefinition) -> Self {
+        Self {
+            pending: definition.burst as f32,
+            active: false,
+            one_shot: true,
+            ..Self::new(definition)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.one_shot && self.pending < 1. && self.particles.is_empty()
+    }
+
+    /// Moves and ages the particles, emitting new ones around `origin`
+    pub fn update(&mut self, delta: f32, origin: Vec2, rng: &mut impl Rng) {
+        let definition = &self.definition;
+
+        for particle in self.particles.iter_mut() {
+            particle.velocity += definition.gravity * delta;
+            particle.pos += particle.velocity * delta;
+            particle.age += delta;
+        }
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+
+        if self.active {
+            self.pending += definition.rate * self.intensity * delta;
+        }
+
+        while self.pending >= 1. {
+            self.pending -= 1.;
+
+            let angle = (definition.direction + rng.gen_range(-0.5..=0.5) * definition.spread).to_radians();
+            let speed = rng.gen_range(definition.speed.0..=definition.speed.1);
+            let offset = Vec2::new(
+                rng.gen_range(-1.0..=1.) * definition.area.x,
+                rng.gen_range(-1.0..=1.) * definition.area.y,
+            );
+
+            self.particles.push(Particle {
+                pos: origin + offset,
+                velocity: Vec2::from_angle(angle) * speed,
+                age: 0.,
+                lifetime: rng.gen_range(definition.lifetime.0..=definition.lifetime.1),
+            });
+        }
+    }
+
+    /// A quad for each particle, relative to `origin`
+    fn build_mesh(&self, origin: Vec2, mesh: &mut Mesh) {
+        let uv = self.uv.unwrap_or(Rect::new(0., 0., 1., 1.));
+        let half_size = self.definition.size / 2.;
+
+        let mut positions = Vec::with_capacity(self.particles.len() * 4);
+        let mut uvs = Vec::with_capacity(self.particles.len() * 4);
+        let mut colors = Vec::with_capacity(self.particles.len() * 4);
+        let mut indices = Vec::with_capacity(self.particles.len() * 6);
+
+        for (i, particle) in self.particles.iter().enumerate() {
+            let center = particle.pos - origin;
+            let color = self.definition.color_at(particle.age / particle.lifetime);
+
+            // Stretched particles are turned along their velocity
+            let direction = particle.velocity.normalize_or_zero();
+            let (along, across) = if self.definition.stretch != 1. && direction != Vec2::ZERO {
+                (direction * self.definition.stretch, direction.perp())
+            } else {
+                (Vec2::X, Vec2::Y)
+            };
+
+            for (corner, u, v) in [
+                (Vec2::new(-1., -1.), uv.min.x, uv.max.y),
+                (Vec2::new(1., -1.), uv.max.x, uv.max.y),
+                (Vec2::new(1., 1.), uv.max.x, uv.min.y),
+                (Vec2::new(-1., 1.), uv.min.x, uv.min.y),
+            ] {
+                let offset = (along * corner.x + across * corner.y) * half_size;
+                positions.push((center + offset).extend(0.).to_array());
+                uvs.push([u, v]);
+                colors.push(color.as_linear_rgba_f32());
+            }
+
+            let first = i as u32 * 4;
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        mesh.set_indices(Some(Indices::U32(indices)));
+    }
+}
+
+/// Kicks up dust while the player runs
+#[derive(Component)]
+pub struct DustEmitter;
+
+/// Fills the view around the camera, wherever it goes
+#[derive(Component)]
+pub struct AmbientEmitter;
+
+#[derive(Component)]
+pub struct Fireflies;
+
+/// Only emits where it rains
+#[derive(Component)]
+pub struct Rain;
+
+fn emitter(definitions: &Option<Res<ParticleDefinitions>>, name: &str) -> Option<EmitterDefinition> {
+    let definition = definitions
+        .as_ref()
+        .and_then(|definitions| definitions.emitters.get(name).cloned());
+
+    if definition.is_none() {
+        warn!("Unknown {name} emitter");
+    }
+
+    definition
+}
+
+/// Gives the new emitters the mesh and the material to draw their particles with
+pub fn prepare_emitters(
+    mut commands: Commands,
+    mut emitter_q: Query<(Entity, &mut ParticleEmitter), Added<ParticleEmitter>>,
+    assets: Res<GameAssets>,
+    atlases: Res<Assets<TextureAtlas>>,
+    textures: Res<Assets<TexturePack>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let pack = assets.pack(GameAssetType::Particles, &textures);
+    let atlas = atlases
+        .get(assets.get(GameAssetType::Particles))
+        .expect("Texture atlas must exist");
+
+    for (entity, mut emitter) in emitter_q.iter_mut() {
+        if let Some(rect) = pack
+            .get_index(&emitter.definition.frame)
+            .map(|index| atlas.textures[index])
+        {
+            emitter.uv = Some(Rect::from_corners(rect.min / atlas.size, rect.max / atlas.size));
+        } else {
+            warn!("Unknown {} frame", emitter.definition.frame);
+        }
+
+        commands.entity(entity).insert((
+            Mesh2dHandle(meshes.add(Mesh::new(PrimitiveTopology::TriangleList))),
+            materials.add(ColorMaterial {
+                color: Color::WHITE,
+                texture: Some(atlas.texture.clone()),
+            }),
+        ));
+    }
+}
+
+pub fn update_emitters(
+    mut commands: Commands,
+    mut emitter_q: Query<(Entity, &mut ParticleEmitter, &GlobalTransform)>,
+    time: Res<Time>,
+    mut rng: ResMut<GameRng>,
+) {
+    let delta = time.delta_seconds();
+
+    for (entity, mut emitter, transform) in emitter_q.iter_mut() {
+        emitter.update(
+            delta,
+            transform.translation().truncate(),
+            rng.stream(RngStream::Effects),
+        );
+
+        if emitter.is_finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+pub fn render_emitters(
+    mut emitter_q: Query<(&ParticleEmitter, &GlobalTransform, &Mesh2dHandle, &mut Visibility)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (emitter, transform, handle, mut visibility) in emitter_q.iter_mut() {
+        // Empty meshes are not drawn at all
+        if emitter.len() == 0 {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            emitter.build_mesh(transform.translation().truncate(), mesh);
+        }
+    }
+}
+
+pub fn spawn_dust_emitter(
+    mut commands: Commands,
+    player_q: Query<Entity, Added<Player>>,
+    definitions: Option<Res<ParticleDefinitions>>,
+) {
+    for player in player_q.iter() {
+        let Some(definition) = emitter(&definitions, "dust") else {
+            return;
+        };
+
+        let emitter = commands
+            .spawn((
+                ParticleEmitter::new(definition),
+                DustEmitter,
+                // At the feet, behind the player
+                SpatialBundle::from_transform(Transform::from_xyz(0., -TILE_SIZE / 2., -0.5)),
+            ))
+            .id();
+        commands.entity(player).add_child(emitter);
+    }
+}
+
+pub fn update_dust_emitter(
+    player_q: Query<&Velocity, With<Player>>,
+    mut emitter_q: Query<&mut ParticleEmitter, With<DustEmitter>>,
+) {
+    let Ok(velocity) = player_q.get_single() else {
+        return;
+    };
+
+    for mut emitter in emitter_q.iter_mut() {
+        emitter.active = velocity.linvel != Vec2::ZERO;
+    }
+}
+
+pub fn emit_hit_sparks(
+    mut commands: Commands,
+    transform_q: Query<&GlobalTransform>,
+    definitions: Option<Res<ParticleDefinitions>>,
+    mut weapon_collision_reader: EventReader<WeaponCollision>,
+) {
+    for event in weapon_collision_reader.iter() {
+        let Ok(transform) = transform_q.get(event.other) else {
+            continue;
+        };
+        let Some(definition) = emitter(&definitions, "sparks") else {
+            return;
+        };
+
+        commands.spawn((
+            ParticleEmitter::one_shot(definition),
+            SpatialBundle::from_transform(Transform::from_translation(transform.translation() + Vec3::Z)),
+        ));
+    }
+}
+
+/// Lets leaves fall from the trees, i.e. the objects breaking into leaves
+pub fn spawn_leaf_emitters(
+    mut commands: Commands,
+    tile_q: Query<(Entity, &Destructible), Added<Destructible>>,
+    metadata: Res<TileMetadata>,
+    definitions: Option<Res<ParticleDefinitions>>,
+) {
+    for (entity, tile) in tile_q.iter() {
+        let is_tree = tile.layer == LayerType::Objects
+            && metadata
+                .get(tile.layer, tile.cell)
+                .is_some_and(|definition| matches!(definition.particle, Some(BreakParticle::Leaf)));
+        if !is_tree {
+            continue;
+        }
+
+        let Some(definition) = emitter(&definitions, "leaves") else {
+            return;
+        };
+
+        let emitter = commands
+            .spawn((
+                ParticleEmitter::new(definition),
+                SpatialBundle::from_transform(Transform::from_xyz(0., TILE_SIZE / 2., 0.5)),
+            ))
+            .id();
+        commands.entity(entity).add_child(emitter);
+    }
+}
+
+pub fn spawn_ambient_emitters(
+    mut commands: Commands,
+    definitions: Option<Res<ParticleDefinitions>>,
+    size: Res<MapSize>,
+) {
+    let transform = Transform::from_xyz(0., 0., above_map(&size) + AMBIENT_PARTICLES_DEPTH);
+
+    if let Some(definition) = emitter(&definitions, "fireflies") {
+        commands.spawn((
+            ParticleEmitter::new(definition),
+            AmbientEmitter,
+            Fireflies,
+            Emissive,
+            SpatialBundle::from_transform(transform),
+        ));
+    }
+
+    if let Some(definition) = emitter(&definitions, "rain") {
+        let mut emitter = ParticleEmitter::new(definition);
+        emitter.active = false;
+
+        commands.spawn((emitter, AmbientEmitter, Rain, SpatialBundle::from_transform(transform)));
+    }
+}
+
+pub fn follow_camera(
+    camera_q: Query<&Transform, (With<Camera>, Without<AmbientEmitter>)>,
+    mut emitter_q: Query<&mut Transform, With<AmbientEmitter>>,
+) {
+    let Ok(camera) = camera_q.get_single() else {
+        return;
+    };
+
+    for mut transform in emitter_q.iter_mut() {
+        transform.translation.x = camera.translation.x;
+        transform.translation.y = camera.translation.y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    #[test]
+    fn emit_move_and_expire() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut emitter = ParticleEmitter::new(EmitterDefinition {
+            rate: 10.,
+            lifetime: (1., 1.),
+            speed: (10., 10.),
+            direction: 0.,
+            gravity: Vec2::new(0., -10.),
+            colors: vec![[1., 1., 1., 1.], [1., 1., 1., 0.]],
+            ..default()
+        });
+
+        emitter.update(0.5, Vec2::ZERO, &mut rng);
+        assert_eq!(emitter.len(), 5);
+
+        emitter.active = false;
+        emitter.update(0.25, Vec2::ZERO, &mut rng);
+        let particle = &emitter.particles[0];
+        assert_eq!(particle.pos, Vec2::new(2.5, -0.625));
+        assert_eq!(emitter.definition.color_at(0.25).a(), 0.75);
+
+        emitter.update(1., Vec2::ZERO, &mut rng);
+        assert_eq!(emitter.len(), 0);
+        assert!(!emitter.is_finished());
+    }
+}
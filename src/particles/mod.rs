@@ -0,0 +1,267 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{prelude::*, reflect::TypeUuid};
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    collisions::MAGIC_COLLISION_GROUP,
+    constants::{ANIMATION_DURATION, GLOW_COLOR, TILE_SIZE},
+    daytime::Emissive,
+    depth::YSort,
+    entities::{AttackType, Enemy},
+    events::EmitParticleEffect,
+    frames::TexturePack,
+    magic::{Magic, PlayerMagic},
+    rng::{GameRng, RngStream},
+    GameAssetType,
+    GameAssets,
+};
+
+pub use emitter::*;
+
+mod emitter;
+
+/// How a particle effect is played, by name of its frames in the `particles` texture pack
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ParticleDefinition {
+    /// Seconds each frame is shown
+    pub frame_duration: f32,
+    /// In tiles, from where the effect is emitted
+    pub offset: Vec2,
+    /// Randomly flips the frames horizontally
+    pub flip: bool,
+    /// How many times the frames are played, before despawning
+    #[serde(rename = "loop")]
+    pub loops: u32,
+    pub scale: f32,
+    /// Fades out over the last loop
+    pub fade: bool,
+    /// Brighter than the other sprites and untouched by the night, though still alpha blended like them
+    pub glow: bool,
+}
+
+impl Default for ParticleDefinition {
+    fn default() -> Self {
+        Self {
+            frame_duration: ANIMATION_DURATION.as_secs_f32(),
+            offset: Vec2::ZERO,
+            flip: false,
+            loops: 1,
+            scale: 1.,
+            fade: false,
+            glow: false,
+        }
+    }
+}
+
+/// The definitions of the particle effects, falling back to the default ones for those not listed, and of the emitters
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[uuid = "b1569168-e38f-4387-aa88-ec07234a8220"]
+pub struct ParticleDefinitions {
+    #[serde(default)]
+    pub effects: HashMap<String, ParticleDefinition>,
+    #[serde(default)]
+    pub emitters: HashMap<String, EmitterDefinition>,
+}
+
+impl ParticleDefinition {
+    /// The color of the sprites of the effect, when they spawn
+    pub fn color(&self) -> Color {
+        if self.glow {
+            GLOW_COLOR
+        } else {
+            Color::WHITE
+        }
+    }
+}
+
+impl ParticleDefinitions {
+    pub fn get(&self, name: &str) -> ParticleDefinition {
+        self.effects.get(name).cloned().unwrap_or_default()
+    }
+}
+
+#[derive(Component)]
+pub struct ParticleEffectAnimation {
+    /// Indices of the frames in the atlas
+    frames: Vec<usize>,
+    current_frame: usize,
+    loops: u32,
+    timer: Timer,
+    finished: bool,
+    offset: Vec3,
+    fade: bool,
+    /// The opacity of the sprites when they spawn, before fading
+    opacity: f32,
+}
+
+impl ParticleEffectAnimation {
+    pub fn new(frames: Vec<usize>, definition: &ParticleDefinition, offset: Vec3) -> Self {
+        let timer = Timer::new(Duration::from_secs_f32(definition.frame_duration), TimerMode::Repeating);
+
+        Self {
+            frames,
+            current_frame: 0,
+            loops: definition.loops.max(1),
+            timer,
+            finished: false,
+            offset,
+            fade: definition.fade,
+            opacity: definition.color().a(),
+        }
+    }
+
+    /// The index in the atlas of the frame to show
+    pub fn next_frame(&mut self, delta: Duration) -> usize {
+        self.timer.tick(delta);
+
+        if self.timer.just_finished() {
+            self.current_frame += 1;
+            if self.current_frame == self.frames.len() {
+                self.loops -= 1;
+                self.current_frame = 0;
+                self.finished = self.loops == 0;
+            }
+        }
+
+        self.frames[self.current_frame]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// The opacity of the current frame, fading out over the last loop when needed
+    pub fn alpha(&self) -> f32 {
+        if self.fade && self.loops == 1 {
+            self.opacity * (1. - self.current_frame as f32 / self.frames.len() as f32)
+        } else {
+            self.opacity
+        }
+    }
+}
+
+pub enum ParticleEffect {
+    Aura,
+    EnemyAttack(AttackType),
+    EnemyDeath(Enemy),
+    Flame,
+    Heal,
+    Leaf,
+    Smoke,
+    Sparkle,
+}
+
+impl ParticleEffect {
+    /// The name of the effect, both in the definitions and in the texture pack
+    pub fn name(&self) -> String {
+        match self {
+            Self::Aura => "aura".to_string(),
+            Self::Leaf => "leaf".to_string(),
+            Self::EnemyAttack(attack_type) => format!("{attack_type}_attack"),
+            Self::EnemyDeath(enemy) => format!("{}_death", enemy.ty.sprite()),
+            Self::Flame => "flame".to_string(),
+            Self::Heal => "heal".to_string(),
+            Self::Smoke => "smoke".to_string(),
+            Self::Sparkle => "sparkle".to_string(),
+        }
+    }
+}
+
+pub fn spawn_particles(
+    mut commands: Commands,
+    assets: Res<GameAssets>,
+    textures: Res<Assets<TexturePack>>,
+    definitions: Option<Res<ParticleDefinitions>>,
+    mut particle_effect_reader: EventReader<EmitParticleEffect>,
+    mut rng: ResMut<GameRng>,
+) {
+    let pack = assets.pack(GameAssetType::Particles, &textures);
+    let atlas_handle = assets.get(GameAssetType::Particles);
+
+    for event in particle_effect_reader.iter() {
+        let particle = &event.ty;
+        let name = particle.name();
+        let definition = definitions
+            .as_ref()
+            .map(|definitions| definitions.get(&name))
+            .unwrap_or_default();
+
+        // Some effects come in a few variants, e.g. `leaf1` to `leaf6`
+        let variants = pack.variants("particles", &name);
+        if variants.is_empty() {
+            warn!("Unknown {name} particle");
+            continue;
+        }
+        let variant = &variants[rng.stream(RngStream::Effects).gen_range(0..variants.len())];
+        let frames = pack.frame_indices(&format!("particles/{variant}"));
+        let Some((_, frame)) = frames.first().and_then(|index| pack.frame_at(*index)) else {
+            warn!("No frames for {variant} particle");
+            continue;
+        };
+
+        let mut sprite = TextureAtlasSprite::new(frames[0]);
+        sprite.flip_x = definition.flip && rng.stream(RngStream::Effects).gen_range(0..=1) == 1;
+        sprite.color = definition.color();
+
+        let pos = event.pos + (definition.offset * TILE_SIZE).extend(1.);
+        let mut cmd = commands.spawn((
+            SpriteSheetBundle {
+                sprite,
+                texture_atlas: atlas_handle.clone(),
+                transform: Transform::from_translation(pos).with_scale(Vec3::splat(definition.scale)),
+                ..Default::default()
+            },
+            ParticleEffectAnimation::new(frames, &definition, event.offset),
+            YSort::default(),
+        ));
+
+        if definition.glow {
+            cmd.insert(Emissive);
+        }
+
+        match particle {
+            ParticleEffect::Flame => {
+                cmd.insert((
+                    Magic::Flame,
+                    PlayerMagic,
+                    Collider::cuboid(frame.frame.w / 2.0, frame.frame.h / 2.0),
+                    MAGIC_COLLISION_GROUP.clone(),
+                    ActiveEvents::COLLISION_EVENTS,
+                    // Without a body, it's a fixed collider, like the tiles it burns
+                    ActiveCollisionTypes::default() | ActiveCollisionTypes::STATIC_STATIC,
+                    ColliderDebugColor(Color::INDIGO),
+                ));
+            },
+            _ => {},
+        }
+    }
+}
+
+pub fn animate_particles(
+    mut commands: Commands,
+    mut particle_q: Query<(
+        Entity,
+        &mut TextureAtlasSprite,
+        &mut ParticleEffectAnimation,
+        &mut Transform,
+    )>,
+    time: Res<Time>,
+) {
+    let delta = time.delta();
+    for (entity, mut sprite, mut animation, mut transform) in particle_q.iter_mut() {
+        let index = animation.next_frame(delta);
+
+        if animation.is_finished() {
+            commands.entity(entity).despawn_recursive();
+        } else if index != sprite.index {
+            transform.translation += animation.offset * TILE_SIZE;
+
+            sprite.index = index;
+            sprite.color.set_a(animation.alpha());
+        }
+    }
+}
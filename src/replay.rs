@@ -0,0 +1,227 @@
+use std::{fs::File, time::Instant};
+
+use bevy::{
+    app::AppExit,
+    input::{keyboard::KeyboardInput, ButtonState},
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::FRAME_DURATION,
+    rng::GameRng,
+    AppState,
+};
+
+/// A key pressed or released by the player
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RecordedInput {
+    /// Frames since the level started
+    pub frame: u64,
+    /// Seconds since the level started
+    pub time: f32,
+    pub key: KeyCode,
+    pub pressed: bool,
+}
+
+/// A played session, that can be fed back to the game to reproduce it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub seed: u64,
+    pub inputs: Vec<RecordedInput>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> Self {
+        let file = File::open(path).unwrap_or_else(|_| panic!("File {path} does not exist"));
+
+        serde_json::from_reader(file).expect("Invalid recording")
+    }
+
+    pub fn save(&self, path: &str) {
+        let result = File::create(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| serde_json::to_writer_pretty(file, self).map_err(anyhow::Error::from));
+
+        match result {
+            Ok(_) => info!("Recording saved to {path}"),
+            Err(e) => warn!("Cannot save recording to {path}: {e}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ReplayMode {
+    /// Records the inputs, and saves them on exit when a path is given
+    Record(Option<String>),
+    Replay(Recording),
+}
+
+/// Frames since the level started, the clock of both recording and replay
+#[derive(Debug, Default, Resource)]
+pub struct LevelFrame(pub u64);
+
+#[derive(Resource)]
+pub struct Recorder {
+    pub recording: Recording,
+    path: Option<String>,
+}
+
+#[derive(Resource)]
+pub struct Replayer {
+    recording: Recording,
+    next: usize,
+}
+
+impl Replayer {
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.recording.inputs.len()
+    }
+}
+
+/// Every frame lasts exactly `FRAME_DURATION`, however long it takes to run, for physics and timers to be reproducible
+pub struct FixedTimestepPlugin;
+
+impl Plugin for FixedTimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeUpdateStrategy::ManualInstant(Instant::now()))
+            .add_system(advance_time.in_base_set(CoreSet::First).before(TimeSystem))
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::Fixed {
+                    dt: FRAME_DURATION.as_secs_f32(),
+                    substeps: 1,
+                },
+                ..default()
+            });
+    }
+}
+
+fn advance_time(mut strategy: ResMut<TimeUpdateStrategy>) {
+    if let TimeUpdateStrategy::ManualInstant(instant) = *strategy {
+        *strategy = TimeUpdateStrategy::ManualInstant(instant + FRAME_DURATION);
+    }
+}
+
+pub struct ReplayPlugin {
+    pub mode: ReplayMode,
+}
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<FixedTimestepPlugin>() {
+            app.add_plugin(FixedTimestepPlugin);
+        }
+
+        app.init_resource::<LevelFrame>().add_system(
+            count_frames
+                .in_base_set(CoreSet::First)
+                .run_if(in_state(AppState::RunLevel)),
+        );
+
+        match &self.mode {
+            ReplayMode::Record(path) => {
+                app.insert_resource(Recorder {
+                    recording: Recording::default(),
+                    path: path.clone(),
+                })
+                .add_system(start_recording.in_schedule(OnEnter(AppState::RunLevel)))
+                .add_system(
+                    record_inputs
+                        .in_base_set(CoreSet::First)
+                        .after(count_frames)
+                        .run_if(in_state(AppState::RunLevel)),
+                )
+                .add_system(save_recording.in_base_set(CoreSet::Last));
+            },
+            ReplayMode::Replay(recording) => {
+                info!(
+                    "Replaying {} inputs with seed {}",
+                    recording.inputs.len(),
+                    recording.seed
+                );
+
+                app.insert_resource(GameRng::new(recording.seed))
+                    .insert_resource(Replayer {
+                        recording: recording.clone(),
+                        next: 0,
+                    })
+                    .add_system(
+                        replay_inputs
+                            .in_base_set(CoreSet::First)
+                            .after(count_frames)
+                            .run_if(in_state(AppState::RunLevel)),
+                    );
+            },
+        }
+    }
+}
+
+fn count_frames(mut frame: ResMut<LevelFrame>) {
+    frame.0 += 1;
+}
+
+fn record_inputs(
+    time: Res<Time>,
+    frame: Res<LevelFrame>,
+    mut recorder: ResMut<Recorder>,
+    mut keyboard_reader: EventReader<KeyboardInput>,
+) {
+    for event in keyboard_reader.iter() {
+        let Some(key) = event.key_code else {
+            continue;
+        };
+
+        recorder.recording.inputs.push(RecordedInput {
+            frame: frame.0,
+            time: time.elapsed_seconds(),
+            key,
+            pressed: event.state == ButtonState::Pressed,
+        });
+    }
+}
+
+fn start_recording(mut recorder: ResMut<Recorder>, rng: Res<GameRng>) {
+    recorder.recording.seed = rng.seed();
+}
+
+fn save_recording(recorder: Res<Recorder>, mut exit_reader: EventReader<AppExit>) {
+    if exit_reader.iter().last().is_none() {
+        return;
+    }
+
+    if let Some(path) = &recorder.path {
+        recorder.recording.save(path);
+    }
+}
+
+fn replay_inputs(
+    frame: Res<LevelFrame>,
+    mut replayer: ResMut<Replayer>,
+    mut keyboard_events: ResMut<Events<KeyboardInput>>,
+) {
+    // The player is only watching
+    keyboard_events.clear();
+
+    while let Some(input) = replayer.recording.inputs.get(replayer.next).copied() {
+        if input.frame > frame.0 {
+            break;
+        }
+
+        keyboard_events.send(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(input.key),
+            state: if input.pressed {
+                ButtonState::Pressed
+            } else {
+                ButtonState::Released
+            },
+        });
+        replayer.next += 1;
+
+        if replayer.is_finished() {
+            info!("Replay finished");
+        }
+    }
+}
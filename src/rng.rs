@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use enum_iterator::{all, Sequence};
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Independent sequences of random numbers, so that e.g. a particle effect doesn't change the outcome of a fight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Sequence)]
+pub enum RngStream {
+    Map,
+    Combat,
+    Loot,
+    Spawning,
+    Effects,
+}
+
+/// The random number generator behind every random choice of the game, so that a run can be reproduced from its seed
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    streams: HashMap<RngStream, StdRng>,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        let streams = all::<RngStream>()
+            .map(|stream| (stream, StdRng::seed_from_u64(stream_seed(seed, stream))))
+            .collect();
+
+        Self { seed, streams }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut StdRng {
+        self.streams.get_mut(&stream).expect("Every stream is seeded")
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(rand::random())
+    }
+}
+
+fn stream_seed(seed: u64, stream: RngStream) -> u64 {
+    seed ^ (stream as u64 + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+pub fn log_seed(rng: Res<GameRng>) {
+    info!("Random seed: {}", rng.seed());
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    fn sample(rng: &mut GameRng, stream: RngStream) -> Vec<u32> {
+        (0..8).map(|_| rng.stream(stream).gen()).collect()
+    }
+
+    #[test]
+    fn streams_are_reproducible_and_independent() {
+        let mut rng = GameRng::new(42);
+        let map = sample(&mut rng, RngStream::Map);
+
+        let mut other = GameRng::new(42);
+        sample(&mut other, RngStream::Effects);
+
+        assert_eq!(sample(&mut other, RngStream::Map), map);
+        assert_ne!(sample(&mut GameRng::new(43), RngStream::Map), map);
+    }
+}
@@ -4,8 +4,19 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
-    constants::SPEED,
-    entities::{Animation, AttackTimer, CastSpellTimer, Direction, Player, PlayerStat, Status},
+    animation::Animation,
+    constants::{DODGE_COST, DODGE_DURATION, DODGE_SPEED_FACTOR, HIT_INPUT_LOCK, SPEED},
+    entities::{
+        AttackTimer,
+        CastSpellTimer,
+        Direction,
+        DodgeCooldown,
+        DodgeTimer,
+        HitTimer,
+        Player,
+        PlayerStat,
+        Status,
+    },
     events::{SwitchMagic, SwitchWeapon},
     screens::GameMode,
     weapon::Weapon,
@@ -13,10 +24,24 @@ use crate::{
     StaticCollider,
 };
 
+type PlayerInputQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static mut Player,
+        &'static mut Velocity,
+        &'static mut Animation,
+        Option<&'static DodgeCooldown>,
+        Option<&'static HitTimer>,
+    ),
+    Without<StaticCollider>,
+>;
+
 pub fn handle_input(
     mut commands: Commands,
     mut keyboard_input: ResMut<Input<KeyCode>>,
-    mut query: Query<(Entity, &mut Player, &mut Velocity, &mut Animation), Without<StaticCollider>>,
+    mut query: PlayerInputQuery,
     mut switch_weapon: EventWriter<SwitchWeapon>,
     mut switch_magic: EventWriter<SwitchMagic>,
     weapon: Res<Weapon>,
@@ -24,7 +49,12 @@ pub fn handle_input(
 ) {
     let mut vec = Vec2::default();
 
-    let (entity, mut player, mut velocity, mut animation) = query.single_mut();
+    let (entity, mut player, mut velocity, mut animation, dodge_cooldown, hit_timer) = query.single_mut();
+
+    // The dodge goes on until its timer ends, and the knockback right after a hit
+    if player.is_dodging() || hit_timer.is_some_and(|timer| timer.elapsed() < HIT_INPUT_LOCK) {
+        return;
+    }
 
     if player.is_attacking() || player.is_casting_spell() {
         velocity.linvel = Vec2::ZERO;
@@ -59,21 +89,35 @@ pub fn handle_input(
         Status::Move(vec)
     };
 
+    // A single action at once, whichever key comes first
     for key in keyboard_input.get_just_pressed() {
         match key {
-            KeyCode::Space => {
+            KeyCode::Space if status.is_free() => {
                 status = Status::Attack;
                 commands.entity(entity).insert(AttackTimer(Timer::new(
                     player.attack_cooldown().add(weapon.cooldown()),
                     TimerMode::Once,
                 )));
             },
-            KeyCode::LControl => {
+            KeyCode::LControl if status.is_free() => {
                 status = Status::CastSpell;
                 commands
                     .entity(entity)
                     .insert(CastSpellTimer(Timer::new(player.attack_cooldown(), TimerMode::Once)));
             },
+            KeyCode::LShift if status.is_free() && dodge_cooldown.is_none() && player.spend_energy(DODGE_COST) => {
+                // Standing still, the player dodges forward
+                let direction = if vec == Vec2::ZERO {
+                    player.direction.as_vec2()
+                } else {
+                    vec.normalize()
+                };
+
+                status = Status::Dodge(direction);
+                commands
+                    .entity(entity)
+                    .insert(DodgeTimer(Timer::new(DODGE_DURATION, TimerMode::Once)));
+            },
             KeyCode::Q => {
                 switch_weapon.send(SwitchWeapon);
             },
@@ -93,10 +137,14 @@ pub fn handle_input(
         player.status = status;
         animation.stop();
 
-        if player.is_moving() {
-            velocity.linvel = vec * player.stats.limit(PlayerStat::Speed) as f32 * SPEED;
+        let speed = player.stats.limit(PlayerStat::Speed) as f32 * SPEED;
+
+        velocity.linvel = if player.is_moving() {
+            vec * speed
+        } else if let Status::Dodge(direction) = player.status {
+            direction * speed * DODGE_SPEED_FACTOR
         } else {
-            velocity.linvel = Vec2::ZERO;
-        }
+            Vec2::ZERO
+        };
     }
 }
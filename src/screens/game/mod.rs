@@ -1,5 +1,6 @@
 use bevy::{
     app::App,
+    ecs::schedule::common_conditions::not,
     prelude::{Plugin, *},
     window::PrimaryWindow,
 };
@@ -9,53 +10,91 @@ use enum_iterator::Sequence;
 use parse_display::Display;
 
 use crate::{
-    camera::{move_camera, spawn_camera},
+    camera::{move_camera, shake_camera, spawn_camera, zoom_camera},
     collisions::{
         damage_attackable,
         handle_collisions,
         handle_magic_collisions,
         handle_weapon_collisions,
         kill_attackable,
-        OBJECTS_COLLISION_GROUP,
     },
+    console::{execute_enemy_commands, execute_player_commands},
     constants::TILE_SIZE,
-    debug::{can_spawn, MAX_ENEMIES, MAX_TILES},
+    daytime::{advance_time_of_day, tint_world, wake_fireflies},
+    debug::can_spawn,
+    depth::{anchor_y_sort, update_child_depth, update_depth},
     entities::{
+        cool_down_dodge,
         end_enemy_attack,
         end_player_attack,
+        end_player_dodge,
         end_player_spell_cast,
         from_position,
         handle_enemy_hit,
         handle_player_hit,
+        land_enemy_attack,
+        lock_boss_arena,
         move_enemy,
+        remove_empty_arenas,
         render_enemy,
         render_player,
+        reward_boss_defeat,
+        spawn_boss_arena,
         spawn_enemy,
         spawn_player,
-        update_depth,
-        Attackable,
+        update_boss_phase,
+        EnemyType,
     },
-    frames::TexturePack,
+    events::{RespawnLayer, RestartLevel},
+    layer,
     magic::{cast_spell, recover_energy, switch_magic},
-    map::{LayerType, WorldMap},
-    particles::{animate_particles, spawn_particles},
-    screens::{game::input::handle_input, is_playing},
+    map::{LayerType, Level, MapAssets, WorldMap},
+    minimap::{resize_minimap, reveal_minimap, spawn_minimap, toggle_world_map, update_minimap_markers},
+    music::{update_music, MusicController},
+    particles::{
+        animate_particles,
+        emit_hit_sparks,
+        follow_camera,
+        prepare_emitters,
+        render_emitters,
+        spawn_ambient_emitters,
+        spawn_dust_emitter,
+        spawn_leaf_emitters,
+        spawn_particles,
+        update_dust_emitter,
+        update_emitters,
+    },
+    screens::{game::input::handle_input, is_editing, is_in_game, is_playing, GameMode},
+    spawner::{spawn_spawners, update_spawners, Spawners},
+    tiles::{
+        break_destructibles,
+        collect_pickups,
+        regrow_tiles,
+        schedule_regrowth,
+        spawn_drops,
+        spawn_tile,
+        LevelContext,
+        Regrowth,
+    },
     ui::{
         change_magic_item,
         change_weapon_item,
         end_switch_magic,
         end_switch_weapon,
         spawn_ui,
+        update_boss_health_ui,
+        update_clock_ui,
         update_energy_ui,
         update_health_ui,
         update_xp_ui,
     },
     weapon::{spawn_weapon, switch_weapon},
+    weather::{extinguish_flames, spawn_fog, sway_grass, update_fog, update_rain, update_weather},
     AppState,
-    GameAssets,
     Layer,
     Map,
     MapSize,
+    Persistent,
 };
 use crate::collisions::{damage_player};
 
@@ -69,22 +108,18 @@ impl Plugin for GameScreenPlugin {
             (
                 // debug_tiles,
                 spawn_ground,
-                spawn_camera,
-                spawn_tiles.after(spawn_camera),
-                spawn_ui.after(spawn_tiles),
+                spawn_tiles,
+                spawn_spawners,
             )
                 .in_schedule(OnEnter(AppState::RunLevel)),
         )
-        .add_systems(
-            (move_camera, update_energy_ui, update_health_ui, update_xp_ui).in_set(OnUpdate(AppState::RunLevel)),
-        )
         .add_systems(
             (
                 switch_weapon.run_if(is_playing),
                 spawn_weapon.run_if(is_playing),
-                change_weapon_item.run_if(is_playing),
-                end_switch_weapon.run_if(is_playing),
                 end_player_attack.run_if(is_playing),
+                end_player_dodge.run_if(is_playing),
+                cool_down_dodge.run_if(is_playing),
                 handle_weapon_collisions.run_if(is_playing),
             )
                 .in_set(OnUpdate(AppState::RunLevel)),
@@ -93,8 +128,6 @@ impl Plugin for GameScreenPlugin {
             (
                 switch_magic.run_if(is_playing),
                 cast_spell.run_if(is_playing),
-                change_magic_item.run_if(is_playing),
-                end_switch_magic.run_if(is_playing),
                 end_player_spell_cast.run_if(is_playing),
                 handle_magic_collisions.run_if(is_playing),
                 recover_energy.run_if(is_playing),
@@ -107,10 +140,13 @@ impl Plugin for GameScreenPlugin {
                 end_enemy_attack.run_if(is_playing),
                 handle_enemy_hit.run_if(is_playing),
                 handle_player_hit.run_if(is_playing),
-                move_enemy.run_if(is_playing),
+                move_enemy.after(update_weather).run_if(is_playing),
                 render_player,
                 render_enemy,
-                update_depth,
+                land_enemy_attack.after(render_enemy).run_if(is_playing),
+                anchor_y_sort.after(render_player).after(render_enemy),
+                update_depth.after(anchor_y_sort),
+                update_child_depth.after(update_depth),
                 handle_collisions.run_if(is_playing),
                 damage_player.run_if(is_playing),
                 damage_attackable.run_if(is_playing),
@@ -119,21 +155,132 @@ impl Plugin for GameScreenPlugin {
                 .in_set(OnUpdate(AppState::RunLevel)),
         )
         .add_systems(
-            (spawn_particles.run_if(is_playing), animate_particles.run_if(is_playing))
+            (
+                lock_boss_arena.run_if(is_playing),
+                remove_empty_arenas.run_if(is_playing),
+                update_boss_phase.after(damage_attackable).run_if(is_playing),
+                reward_boss_defeat.after(kill_attackable).run_if(is_playing),
+                advance_time_of_day.run_if(is_playing),
+                update_weather.run_if(is_playing),
+                update_spawners.after(advance_time_of_day).run_if(is_playing),
+            )
+                .in_set(OnUpdate(AppState::RunLevel)),
+        )
+        .add_systems(
+            (
+                break_destructibles.before(kill_attackable).run_if(is_playing),
+                schedule_regrowth.after(break_destructibles).run_if(is_playing),
+                spawn_drops.after(break_destructibles).run_if(is_playing),
+                collect_pickups.run_if(is_playing),
+                regrow_tiles.run_if(is_playing),
+            )
+                .in_set(OnUpdate(AppState::RunLevel)),
+        )
+        .add_system(toggle_world_map.run_if(is_in_game))
+        .add_systems((execute_player_commands, execute_enemy_commands).in_set(OnUpdate(AppState::RunLevel)))
+        .add_system(
+            restart_level
+                .after(execute_player_commands)
+                .in_set(OnUpdate(AppState::RunLevel)),
+        )
+        .add_systems(
+            (
+                reload_layers.run_if(not(is_editing)),
+                respawn_layers.after(reload_layers),
+            )
                 .in_set(OnUpdate(AppState::RunLevel)),
         );
     }
 }
 
+/// Everything the player sees and hears, on top of the game logic
+pub struct GameViewPlugin;
+
+impl Plugin for GameViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MusicController>()
+            .add_systems(
+                (
+                    spawn_camera,
+                    spawn_ui.after(spawn_tiles),
+                    spawn_minimap.after(spawn_ui),
+                    spawn_ambient_emitters,
+                    spawn_fog,
+                )
+                    .in_schedule(OnEnter(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    zoom_camera,
+                    shake_camera.run_if(is_playing),
+                    move_camera.after(zoom_camera).after(shake_camera),
+                    update_energy_ui,
+                    update_health_ui,
+                    update_xp_ui,
+                    update_boss_health_ui,
+                    update_clock_ui,
+                    update_music,
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    resize_minimap,
+                    reveal_minimap.run_if(is_playing),
+                    update_minimap_markers,
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    change_weapon_item.run_if(is_playing),
+                    end_switch_weapon.run_if(is_playing),
+                    change_magic_item.run_if(is_playing),
+                    end_switch_magic.run_if(is_playing),
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    spawn_particles.run_if(is_playing),
+                    animate_particles.run_if(is_playing),
+                    extinguish_flames.after(spawn_particles).run_if(is_playing),
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            )
+            .add_systems(
+                (
+                    spawn_dust_emitter,
+                    spawn_leaf_emitters,
+                    emit_hit_sparks.run_if(is_playing),
+                    prepare_emitters
+                        .after(spawn_dust_emitter)
+                        .after(spawn_leaf_emitters)
+                        .after(emit_hit_sparks),
+                    update_dust_emitter.run_if(is_playing),
+                    follow_camera.after(move_camera),
+                    update_emitters.after(update_dust_emitter).run_if(is_playing),
+                    render_emitters.after(update_emitters).after(prepare_emitters),
+                    tint_world.after(prepare_emitters),
+                    wake_fireflies,
+                    update_rain,
+                    update_fog.after(move_camera),
+                    sway_grass,
+                )
+                    .in_set(OnUpdate(AppState::RunLevel)),
+            );
+    }
+}
+
 fn spawn_ground(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    map_assets: Res<MapAssets>,
     window: Query<&Window, With<PrimaryWindow>>,
     size: Res<MapSize>,
 ) {
     let Ok(window) = window.get_single() else { return; };
 
-    let handle = asset_server.load("map/ground.png");
+    let handle = map_assets.ground.clone();
 
     let x = (size.width - window.width()) / 2.;
     let y = -((size.height - window.height()) / 2.);
@@ -170,121 +317,196 @@ fn spawn_tiles(
     mut commands: Commands,
     window: Query<&Window, With<PrimaryWindow>>,
     world_map: Res<WorldMap>,
-    assets: Res<GameAssets>,
-    asset_server: Res<AssetServer>,
-    atlases: Res<Assets<TextureAtlas>>,
-    textures: Res<Assets<TexturePack>>,
+    start: Option<Res<Level>>,
+    mut context: LevelContext,
 ) {
     let window = window.single();
-    let mut num_tiles = 0;
-    let mut num_enemies = 0;
+    let mut count = SpawnCount::default();
 
     // Spawn the world
     for (layer_type, layer) in world_map.layers.iter() {
-        for (row_idx, row) in layer.data.iter().enumerate() {
-            for (col_idx, &cell) in row.iter().enumerate() {
-                let x = (col_idx as f32 + 0.5) * TILE_SIZE;
-                let y = (row_idx as f32 + 0.5) * TILE_SIZE;
+        spawn_layer(
+            &mut commands,
+            window,
+            &mut context,
+            start.as_deref(),
+            layer_type,
+            layer,
+            &mut count,
+        );
+    }
+}
 
-                match cell {
-                    0..=20 => {
-                        num_tiles += 1;
-                        if !can_spawn(num_tiles, MAX_TILES) {
-                            continue;
-                        }
-                        spawn_tile(&mut commands, &window, &assets, &atlases, layer_type, cell, x, y);
-                    },
-                    390..=393 => {
-                        num_enemies += 1;
-                        if !can_spawn(num_enemies, MAX_ENEMIES) {
-                            continue;
-                        }
+/// Tiles and enemies spawned so far, to stay within the debug limits
+#[derive(Default)]
+struct SpawnCount {
+    tiles: usize,
+    enemies: usize,
+}
 
-                        spawn_enemy(
-                            &mut commands,
-                            &window,
-                            &asset_server,
-                            &assets,
-                            &atlases,
-                            &textures,
-                            cell,
-                            x,
-                            y,
-                        );
-                    },
-                    394 => {
-                        spawn_player(&mut commands, &window, &assets, x, y);
-                    },
-                    395 => {
-                        spawn_block(&mut commands, &window, &asset_server, layer_type, x, y);
-                    },
-                    _ => {
-                        if cell != -1 {
-                            info!("Not mapped yet: {}", cell);
-                        }
-                    },
-                }
+fn spawn_layer(
+    commands: &mut Commands,
+    window: &Window,
+    context: &mut LevelContext,
+    start: Option<&Level>,
+    layer_type: &LayerType,
+    layer: &layer::Layer,
+    count: &mut SpawnCount,
+) {
+    for (row_idx, row) in layer.data.iter().enumerate() {
+        for (col_idx, &cell) in row.iter().enumerate() {
+            let x = (col_idx as f32 + 0.5) * TILE_SIZE;
+            let y = (row_idx as f32 + 0.5) * TILE_SIZE;
+
+            match cell {
+                0..=20 => {
+                    count.tiles += 1;
+                    if !can_spawn(count.tiles, context.settings.debug.max_tiles) {
+                        continue;
+                    }
+                    spawn_tile(commands, window, context, layer_type, cell, x, y);
+                },
+                390..=393 | 396 => {
+                    // The boss counts as an enemy too
+                    count.enemies += 1;
+                    if !can_spawn(count.enemies, context.settings.debug.max_enemies) {
+                        continue;
+                    }
+
+                    let enemy = spawn_enemy(
+                        commands,
+                        window,
+                        &context.assets,
+                        &context.atlases,
+                        &context.textures,
+                        EnemyType::from(cell),
+                        x,
+                        y,
+                    );
+
+                    if cell == 396 {
+                        spawn_boss_arena(commands, enemy, from_position(x, y, window).truncate());
+                    }
+                },
+                394 => {
+                    // Skipping to a level moves the player there
+                    let (x, y) = start.map_or((x, y), |start| {
+                        ((start.x as f32 + 0.5) * TILE_SIZE, (start.y as f32 + 0.5) * TILE_SIZE)
+                    });
+                    spawn_player(commands, window, &context.assets, x, y);
+                },
+                395 => {
+                    spawn_block(commands, window, &context.asset_server, layer_type, x, y);
+                },
+                _ => {
+                    if cell != -1 {
+                        info!("Not mapped yet: {}", cell);
+                    }
+                },
             }
         }
     }
 }
 
-fn spawn_tile(
-    commands: &mut Commands,
-    window: &Window,
-    assets: &Res<GameAssets>,
-    atlases: &Res<Assets<TextureAtlas>>,
-    layer_type: &LayerType,
-    cell: i32,
-    x: f32,
-    y: f32,
+/// Updates the map with the layers changed on disk
+fn reload_layers(
+    map_assets: Res<MapAssets>,
+    layers: Res<Assets<layer::Layer>>,
+    mut world_map: ResMut<WorldMap>,
+    mut layer_events: EventReader<AssetEvent<layer::Layer>>,
+    mut restart_level_writer: EventWriter<RestartLevel>,
+    mut respawn_layer_writer: EventWriter<RespawnLayer>,
 ) {
-    let index = layer_type.to_index(cell as usize);
-    let asset_type = layer_type.into();
+    for event in layer_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let (Some(layer_type), Some(layer)) = (map_assets.layer_type(handle), layers.get(handle)) else {
+            continue;
+        };
 
-    let atlas_handle = assets.get(asset_type);
-    let atlas = atlases.get(atlas_handle).unwrap();
-    let rect = atlas.textures[index];
-    let offset = (rect.height() - TILE_SIZE) / 2.0;
+        info!("Reloading {layer_type:?} layer");
+        world_map.layers.insert(layer_type, layer.clone());
 
-    let y = y - offset;
+        // The player and the enemies only spawn with the whole level
+        if layer_type == LayerType::Entities {
+            restart_level_writer.send(RestartLevel { reload_map: false });
+        } else {
+            respawn_layer_writer.send(RespawnLayer(layer_type));
+        }
+    }
+}
 
-    let collider_height = TILE_SIZE / 2.0;
+/// Spawns again the tiles of the reloaded layers
+fn respawn_layers(
+    mut commands: Commands,
+    window: Query<&Window, With<PrimaryWindow>>,
+    world_map: Res<WorldMap>,
+    tile_q: Query<(Entity, &Layer)>,
+    regrowth_q: Query<(Entity, &Regrowth)>,
+    mut context: LevelContext,
+    mut respawn_layer_reader: EventReader<RespawnLayer>,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
 
-    let mut cmd = commands.spawn((
-        SpriteSheetBundle {
-            sprite: TextureAtlasSprite::new(index),
-            texture_atlas: atlas_handle.clone(),
-            transform: Transform::from_translation(from_position(x, y, window)),
-            ..Default::default()
-        },
-        RigidBody::Fixed,
-        Layer(*layer_type),
-    ));
+    for RespawnLayer(layer_type) in respawn_layer_reader.iter() {
+        let Some(layer) = world_map.layers.get(layer_type) else {
+            continue;
+        };
 
-    cmd.with_children(|parent| {
-        let mut child = parent.spawn((
-            Collider::cuboid(rect.width() / 2.0, collider_height / 2.0),
-            Transform::from_xyz(0.0, -offset, 0.0),
-            ColliderDebugColor(Color::BLUE),
-        ));
+        for (entity, tile_layer) in tile_q.iter() {
+            if tile_layer.0 == *layer_type {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
 
-        if layer_type.is_attackable() {
-            // println!("Rect: {:?} {:?}", rect.width(), rect.height());
-            child.insert((
-                Collider::cuboid(rect.width() / 2.0, rect.height() / 2.0),
-                OBJECTS_COLLISION_GROUP.clone(),
-                ActiveEvents::COLLISION_EVENTS,
-                ColliderDebugColor(Color::BLACK),
-            ));
+        for (entity, regrowth) in regrowth_q.iter() {
+            if regrowth.tile.layer == *layer_type {
+                commands.entity(entity).despawn();
+            }
         }
-    });
 
-    if layer_type.is_attackable() {
-        cmd.insert(Attackable::new(1));
+        spawn_layer(
+            &mut commands,
+            window,
+            &mut context,
+            None,
+            layer_type,
+            layer,
+            &mut SpawnCount::default(),
+        );
     }
 }
 
+type LevelQuery<'w, 's> = Query<'w, 's, Entity, (Without<Parent>, Without<Window>, Without<Persistent>)>;
+
+fn restart_level(
+    mut commands: Commands,
+    level_q: LevelQuery,
+    mut restart_level_reader: EventReader<RestartLevel>,
+    mut app_state: ResMut<NextState<AppState>>,
+    mut game_mode: ResMut<GameMode>,
+) {
+    let Some(restart) = restart_level_reader.iter().last() else {
+        return;
+    };
+
+    for entity in level_q.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    if restart.reload_map {
+        commands.remove_resource::<MapAssets>();
+        commands.remove_resource::<WorldMap>();
+        commands.remove_resource::<Spawners>();
+    }
+
+    app_state.set(AppState::LoadLevel);
+    *game_mode = GameMode::Loading;
+}
+
 fn spawn_block(
     commands: &mut Commands,
     window: &Window,
@@ -14,12 +14,27 @@ pub enum GameMode {
     Playing,
     Upgrading,
     Paused,
+    Map,
+    Console,
+    Editing,
 }
 
 pub fn is_playing(game_mode: Res<GameMode>) -> bool {
     matches!(*game_mode, GameMode::Playing)
 }
 
+pub fn is_in_game(game_mode: Res<GameMode>) -> bool {
+    matches!(*game_mode, GameMode::Playing | GameMode::Map)
+}
+
+pub fn is_in_console(game_mode: Res<GameMode>) -> bool {
+    matches!(*game_mode, GameMode::Console)
+}
+
+pub fn is_editing(game_mode: Res<GameMode>) -> bool {
+    matches!(*game_mode, GameMode::Editing)
+}
+
 pub fn is_upgrading(game_mode: Res<GameMode>) -> bool {
     matches!(*game_mode, GameMode::Upgrading)
 }
@@ -1,4 +1,7 @@
-use crate::entities::{Player, PlayerStat};
+use crate::{
+    audio::PlaySound,
+    entities::{Player, PlayerStat},
+};
 use bevy::prelude::*;
 
 use crate::screens::{upgrade::ui::UpgradeScreen, GameMode};
@@ -8,6 +11,7 @@ pub fn handle_input(
     mut game_mode: ResMut<GameMode>,
     mut screen_q: Query<&mut UpgradeScreen>,
     mut player_q: Query<&mut Player>,
+    mut play_sound_writer: EventWriter<PlaySound>,
 ) {
     let mut screen = screen_q.single_mut();
     let mut player = player_q.single_mut();
@@ -37,6 +41,7 @@ pub fn handle_input(
 
                     player.xp -= cost;
                     player.upgrade(selected_stat);
+                    play_sound_writer.send(PlaySound::ui("audio/heal.wav"));
 
                     // println!(
                     //     "Upgrading {selected_stat}:
@@ -184,10 +184,10 @@ pub fn show_ui(mut screen_q: Query<&mut Visibility, With<UpgradeScreen>>, game_m
     if game_mode.is_changed() {
         let mut visibility = screen_q.single_mut();
 
-        *visibility = if *game_mode == GameMode::Playing {
-            Visibility::Hidden
-        } else {
+        *visibility = if *game_mode == GameMode::Upgrading {
             Visibility::Inherited
+        } else {
+            Visibility::Hidden
         };
     }
 }
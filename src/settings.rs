@@ -0,0 +1,95 @@
+use std::fs::File;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::debug::DebugSettings;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VolumeSettings {
+    pub master: f64,
+    pub music: f64,
+    pub sfx: f64,
+    pub ui: f64,
+    pub muted: bool,
+}
+
+impl VolumeSettings {
+    pub fn master(&self) -> f64 {
+        if self.muted {
+            0.0
+        } else {
+            self.master
+        }
+    }
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.4,
+            sfx: 1.0,
+            ui: 1.0,
+            muted: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fullscreen: false,
+        }
+    }
+}
+
+/// User preferences, persisted between runs
+#[derive(Debug, Clone, Default, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: VolumeSettings,
+    pub window: WindowSettings,
+    pub debug: DebugSettings,
+}
+
+impl Settings {
+    pub fn load(path: &str) -> Self {
+        let Ok(file) = File::open(path) else {
+            return Self::default();
+        };
+
+        serde_json::from_reader(file).unwrap_or_else(|e| {
+            warn!("Invalid settings in {path}: {e}");
+            Self::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) {
+        let result = File::create(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| serde_json::to_writer_pretty(file, self).map_err(anyhow::Error::from));
+
+        if let Err(e) = result {
+            warn!("Cannot save settings to {path}: {e}");
+        }
+    }
+
+    /// Changes the settings stored in the file only, leaving out e.g. the command line options of this run
+    pub fn update(path: &str, f: impl FnOnce(&mut Self)) {
+        let mut settings = Self::load(path);
+        f(&mut settings);
+        settings.save(path);
+    }
+}
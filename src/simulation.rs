@@ -0,0 +1,480 @@
+use std::time::Duration;
+
+use bevy::{
+    input::{keyboard::KeyboardInput, ButtonState, InputPlugin},
+    prelude::*,
+    window::{PrimaryWindow, WindowResolution},
+};
+use bevy_rapier2d::prelude::Velocity;
+
+use crate::{
+    console::ConsoleCommand,
+    entities::{Enemy, Player, PlayerStat},
+    events::DamagePlayer,
+    layer,
+    map::{LayerType, Level, LevelSelection, MapAssets, WorldMap},
+    replay::{FixedTimestepPlugin, LevelFrame, Recorder, Recording, ReplayMode, ReplayPlugin, Replayer},
+    settings::{Settings, WindowSettings},
+    spawner::Spawners,
+    AppState,
+    Layer,
+    SimulationPlugin,
+};
+
+const MAX_LOADING_FRAMES: usize = 5000;
+
+/// Runs the game logic under `MinimalPlugins`, with a fixed frame duration and no window, rendering or audio
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(HierarchyPlugin)
+            .add_plugin(InputPlugin)
+            .add_plugin(AssetPlugin::default())
+            .add_plugin(ImagePlugin::default())
+            .add_asset::<TextureAtlas>()
+            .add_plugin(FixedTimestepPlugin)
+            .add_plugin(SimulationPlugin);
+
+        // Game logic relies on the window size to position entities
+        let size = WindowSettings::default();
+        app.world.spawn((
+            Window {
+                resolution: WindowResolution::new(size.width as f32, size.height as f32),
+                ..default()
+            },
+            PrimaryWindow,
+        ));
+    }
+}
+
+pub struct Simulation {
+    pub app: App,
+}
+
+impl Simulation {
+    /// Loads the assets and spawns the given map, without spawners
+    pub fn new(world_map: WorldMap) -> Self {
+        Self::build(Some(world_map), None)
+    }
+
+    /// Loads the given layers of the default map through the asset server, without spawners
+    pub fn load(layers: &[LayerType]) -> Self {
+        Self::load_map(LevelSelection::default(), layers)
+    }
+
+    /// Loads the given layers of a map through the asset server, from the files listed in its manifest
+    pub fn load_map(selection: LevelSelection, layers: &[LayerType]) -> Self {
+        let mut settings = Settings::default();
+        settings.debug.layers = layers.to_vec();
+
+        let mut app = App::new();
+        app.add_plugin(HeadlessPlugin)
+            .insert_resource(settings)
+            .insert_resource(selection);
+
+        Self::start(app, None)
+    }
+
+    /// Records the inputs sent to the simulation
+    pub fn record(world_map: WorldMap) -> Self {
+        Self::build(Some(world_map), Some(ReplayMode::Record(None)))
+    }
+
+    /// Feeds the recorded inputs back to the simulation, as the frames go by
+    pub fn replay(world_map: WorldMap, recording: Recording) -> Self {
+        Self::build(Some(world_map), Some(ReplayMode::Replay(recording)))
+    }
+
+    fn build(world_map: Option<WorldMap>, mode: Option<ReplayMode>) -> Self {
+        let mut app = App::new();
+        app.add_plugin(HeadlessPlugin);
+
+        if let Some(world_map) = world_map {
+            app.insert_resource(world_map);
+        }
+
+        Self::start(app, mode)
+    }
+
+    fn start(mut app: App, mode: Option<ReplayMode>) -> Self {
+        app.insert_resource(Spawners::default());
+
+        if let Some(mode) = mode {
+            app.add_plugin(ReplayPlugin { mode });
+        }
+
+        let mut simulation = Self { app };
+
+        for _ in 0..MAX_LOADING_FRAMES {
+            if simulation.app.world.resource::<State<AppState>>().0 == AppState::RunLevel {
+                // Let the spawned entities settle
+                simulation.step(1);
+                return simulation;
+            }
+
+            simulation.step(1);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        panic!("Level not loaded after {MAX_LOADING_FRAMES} frames");
+    }
+
+    pub fn step(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    pub fn press(&mut self, key: KeyCode) {
+        self.send_key(key, ButtonState::Pressed);
+    }
+
+    pub fn release(&mut self, key: KeyCode) {
+        self.send_key(key, ButtonState::Released);
+    }
+
+    /// Presses the key for a single frame
+    pub fn tap(&mut self, key: KeyCode) {
+        self.press(key);
+        self.step(1);
+        self.release(key);
+    }
+
+    /// Holds the key down for the given number of frames
+    pub fn walk(&mut self, key: KeyCode, frames: usize) {
+        self.press(key);
+        self.step(frames);
+        self.release(key);
+        self.step(1);
+    }
+
+    fn send_key(&mut self, key: KeyCode, state: ButtonState) {
+        self.app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key),
+            state,
+        });
+    }
+
+    /// Runs a console command, as if typed by the player
+    pub fn run(&mut self, command: &str) {
+        let command = command.parse::<ConsoleCommand>().expect("Valid command");
+
+        self.app.world.send_event(command);
+        self.step(1);
+    }
+
+    pub fn player(&mut self) -> &Player {
+        self.app.world.query::<&Player>().single(&self.app.world)
+    }
+
+    pub fn num_enemies(&mut self) -> usize {
+        self.app.world.query::<&Enemy>().iter(&self.app.world).count()
+    }
+
+    pub fn num_tiles(&mut self, ty: LayerType) -> usize {
+        self.app
+            .world
+            .query::<&Layer>()
+            .iter(&self.app.world)
+            .filter(|layer| layer.0 == ty)
+            .count()
+    }
+
+    pub fn player_position(&mut self) -> Vec3 {
+        self.app
+            .world
+            .query_filtered::<&Transform, With<Player>>()
+            .single(&self.app.world)
+            .translation
+    }
+
+    pub fn player_velocity(&mut self) -> Vec2 {
+        self.app
+            .world
+            .query_filtered::<&Velocity, With<Player>>()
+            .single(&self.app.world)
+            .linvel
+    }
+
+    /// Hurts the player for a frame, as if attacked by the first enemy
+    pub fn hit_player(&mut self) {
+        let enemy = self
+            .app
+            .world
+            .query_filtered::<Entity, With<Enemy>>()
+            .iter(&self.app.world)
+            .next()
+            .expect("An enemy");
+
+        self.app.world.send_event(DamagePlayer(enemy));
+        self.step(1);
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.app.world.resource::<LevelFrame>().0
+    }
+
+    pub fn recording(&self) -> Recording {
+        self.app.world.resource::<Recorder>().recording.clone()
+    }
+
+    /// Runs the replay until it reaches the given frame
+    pub fn replay_until(&mut self, frame: u64) {
+        while self.frame() < frame {
+            self.step(1);
+        }
+
+        assert!(self.app.world.resource::<Replayer>().is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        constants::{FRAME_DURATION, HIT_DURATION, HIT_INPUT_LOCK, TILE_SIZE},
+        entities::{to_position, AttackTimer, DodgeTimer},
+        tiles::TileMetadata,
+        weather::WeatherRegions,
+    };
+
+    /// The frames it takes for the given duration to be over
+    fn frames(duration: Duration) -> usize {
+        (duration.as_millis() / FRAME_DURATION.as_millis()) as usize + 1
+    }
+
+    #[test]
+    fn spawns_the_map() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+
+        assert_eq!(simulation.num_enemies(), 1);
+        assert!(simulation.player().value_by(PlayerStat::Health) > 0);
+    }
+
+    #[test]
+    fn enemy_attacks_the_player() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let health = simulation.player().value_by(PlayerStat::Health);
+
+        // Get noticed by the enemy, then wait for it
+        simulation.walk(KeyCode::Down, 20);
+        simulation.step(300);
+
+        assert!(simulation.player().value_by(PlayerStat::Health) < health);
+    }
+
+    #[test]
+    fn player_dodges() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let energy = simulation.player().value_by(PlayerStat::Energy);
+        let position = simulation.player_position();
+
+        simulation.tap(KeyCode::LShift);
+        assert!(simulation.player().is_dodging());
+
+        simulation.step(30);
+        assert!(!simulation.player().is_dodging());
+        assert!(simulation.player().value_by(PlayerStat::Energy) < energy);
+        assert_ne!(simulation.player_position(), position);
+    }
+
+    #[test]
+    fn player_attacks_or_dodges() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let energy = simulation.player().value_by(PlayerStat::Energy);
+
+        simulation.press(KeyCode::Space);
+        simulation.press(KeyCode::LShift);
+        simulation.step(1);
+
+        let world = &mut simulation.app.world;
+        let (player, attack, dodge) = world
+            .query::<(&Player, Option<&AttackTimer>, Option<&DodgeTimer>)>()
+            .single(world);
+        assert_ne!(attack.is_some(), dodge.is_some());
+        assert_eq!(player.is_dodging(), dodge.is_some());
+        // No energy spent on a dodge that didn't happen
+        assert_eq!(player.is_dodging(), player.value_by(PlayerStat::Energy) < energy);
+    }
+
+    #[test]
+    fn player_is_invulnerable_after_a_hit() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let health = simulation.player().value_by(PlayerStat::Health);
+
+        simulation.hit_player();
+        let hurt = simulation.player().value_by(PlayerStat::Health);
+        assert!(hurt < health);
+
+        simulation.hit_player();
+        assert_eq!(simulation.player().value_by(PlayerStat::Health), hurt);
+
+        simulation.step(frames(HIT_DURATION));
+        simulation.hit_player();
+        assert!(simulation.player().value_by(PlayerStat::Health) < hurt);
+    }
+
+    #[test]
+    fn player_is_knocked_back() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let position = simulation.player_position();
+
+        // The enemy stands right below the player
+        simulation.hit_player();
+        assert!(simulation.player_velocity().y > 0.);
+        assert_eq!(simulation.player_velocity().x, 0.);
+
+        simulation.step(5);
+        assert!(simulation.player_position().y > position.y);
+    }
+
+    #[test]
+    fn hit_locks_the_input() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+
+        simulation.hit_player();
+        simulation.press(KeyCode::Left);
+        simulation.step(1);
+        assert_eq!(simulation.player_velocity().x, 0.);
+
+        simulation.step(frames(HIT_INPUT_LOCK));
+        assert!(simulation.player_velocity().x < 0.);
+    }
+
+    #[test]
+    fn player_kills_the_enemy() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let xp = simulation.player().xp;
+
+        // Face the enemy, and let it come closer
+        simulation.walk(KeyCode::Down, 20);
+
+        for _ in 0..60 {
+            simulation.tap(KeyCode::Space);
+            simulation.step(40);
+
+            if simulation.num_enemies() == 0 {
+                break;
+            }
+        }
+
+        assert_eq!(simulation.num_enemies(), 0);
+        assert!(simulation.player().xp > xp);
+    }
+
+    #[test]
+    fn console_commands() {
+        let mut simulation = Simulation::new(WorldMap::debug_enemy());
+        let health = simulation.player().value_by(PlayerStat::Health);
+
+        simulation.run("god");
+        simulation.walk(KeyCode::Down, 20);
+        simulation.step(300);
+        assert_eq!(simulation.player().value_by(PlayerStat::Health), health);
+
+        simulation.run("set_stat health 20");
+        assert_eq!(simulation.player().value_by(PlayerStat::Health), 20);
+        // Beyond what the player has unlocked, raising the limit
+        simulation.run("set_stat health 300");
+        assert_eq!(simulation.player().value_by(PlayerStat::Health), 300);
+        assert_eq!(simulation.player().stats.limit(PlayerStat::Health), 300);
+
+        let xp = simulation.player().xp;
+        simulation.run("kill_all");
+        simulation.step(1);
+        assert_eq!(simulation.num_enemies(), 0);
+        assert_eq!(simulation.player().xp, xp);
+
+        simulation.run("spawn raccoon");
+        assert_eq!(simulation.num_enemies(), 1);
+    }
+
+    #[test]
+    fn replays_a_recording() {
+        let mut simulation = Simulation::record(WorldMap::debug_enemy());
+
+        simulation.walk(KeyCode::Down, 20);
+        simulation.tap(KeyCode::LControl);
+        simulation.step(30);
+        simulation.walk(KeyCode::Left, 15);
+        simulation.tap(KeyCode::Space);
+        simulation.step(60);
+
+        let recording = simulation.recording();
+        assert!(!recording.inputs.is_empty());
+
+        let mut replay = Simulation::replay(WorldMap::debug_enemy(), recording);
+        replay.replay_until(simulation.frame());
+
+        assert_eq!(replay.player_position(), simulation.player_position());
+        assert_eq!(
+            replay.player().value_by(PlayerStat::Health),
+            simulation.player().value_by(PlayerStat::Health)
+        );
+        assert_eq!(replay.num_enemies(), simulation.num_enemies());
+    }
+
+    #[test]
+    fn loads_a_map_from_its_manifest() {
+        let selection = LevelSelection {
+            map: "test/map".to_string(),
+            level: Some("corner".to_string()),
+        };
+        let mut simulation = Simulation::load_map(selection, &[LayerType::Blocks, LayerType::Entities]);
+
+        let world = &simulation.app.world;
+        assert_eq!(world.resource::<WorldMap>().size(), (5, 6));
+        assert_eq!(
+            world
+                .resource::<TileMetadata>()
+                .get(LayerType::Grass, 8)
+                .unwrap()
+                .health,
+            3
+        );
+        assert!(world.resource::<WeatherRegions>().0.is_empty());
+
+        let level = world.resource::<Level>();
+        assert_eq!((level.x, level.y), (4, 3));
+
+        // The player starts at the level, rather than where the map puts it
+        let window = simulation
+            .app
+            .world
+            .query::<&Window>()
+            .single(&simulation.app.world)
+            .clone();
+        let position = to_position(simulation.player_position(), &window);
+        assert!(position.distance(Vec2::new(4.5, 3.5) * TILE_SIZE) < 1.);
+    }
+
+    #[test]
+    fn reloads_a_modified_layer() {
+        let mut simulation = Simulation::load(&[LayerType::Blocks, LayerType::Entities]);
+        assert!(simulation.num_tiles(LayerType::Blocks) > 0);
+
+        // As if the file changed on disk
+        let handle = simulation.app.world.resource::<MapAssets>().layers[&LayerType::Blocks].clone();
+        let mut layers = simulation.app.world.resource_mut::<Assets<layer::Layer>>();
+        layers.get_mut(&handle).expect("Layer loaded").data.clear();
+        simulation.step(2);
+
+        assert_eq!(simulation.num_tiles(LayerType::Blocks), 0);
+        assert!(simulation.app.world.resource::<WorldMap>().layers[&LayerType::Blocks]
+            .data
+            .is_empty());
+        assert_eq!(
+            simulation
+                .app
+                .world
+                .query::<&Player>()
+                .iter(&simulation.app.world)
+                .count(),
+            1
+        );
+    }
+}
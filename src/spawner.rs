@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, math::Vec3Swizzles, prelude::*, reflect::TypeUuid, window::PrimaryWindow};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    constants::TILE_SIZE,
+    daytime::{DayCycle, TimeOfDay},
+    debug::can_spawn,
+    entities::{from_position, spawn_enemy, Enemy, EnemyType},
+    frames::TexturePack,
+    map::{LayerType, WorldMap, Zone},
+    rng::{GameRng, RngStream},
+    settings::Settings,
+    GameAssets,
+};
+
+const MAX_SPAWN_ATTEMPTS: usize = 10;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpawnerDefinition {
+    pub zone: Zone,
+    pub enemies: Vec<EnemyType>,
+    pub max_alive: usize,
+    /// Seconds between two spawns
+    pub respawn_delay: f32,
+    #[serde(default = "default_off_camera")]
+    pub off_camera: bool,
+    /// Enemies spawned at night instead, the same as by day when empty
+    #[serde(default)]
+    pub night_enemies: Vec<EnemyType>,
+    #[serde(default)]
+    pub night_max_alive: Option<usize>,
+}
+
+impl SpawnerDefinition {
+    fn enemies(&self, night: bool) -> &[EnemyType] {
+        if night && !self.night_enemies.is_empty() {
+            &self.night_enemies
+        } else {
+            &self.enemies
+        }
+    }
+
+    fn max_alive(&self, night: bool) -> usize {
+        match self.night_max_alive {
+            Some(max_alive) if night => max_alive,
+            _ => self.max_alive,
+        }
+    }
+}
+
+fn default_off_camera() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[uuid = "f296800a-ae7a-45a6-8be7-89983d1a28c6"]
+pub struct Spawners(pub Vec<SpawnerDefinition>);
+
+#[derive(Component)]
+pub struct Spawner {
+    definition: SpawnerDefinition,
+    timer: Timer,
+    next_enemy: usize,
+}
+
+impl Spawner {
+    pub fn new(definition: SpawnerDefinition) -> Self {
+        let timer = Timer::new(Duration::from_secs_f32(definition.respawn_delay), TimerMode::Once);
+
+        Self {
+            definition,
+            timer,
+            next_enemy: 0,
+        }
+    }
+
+    fn next_enemy(&mut self, night: bool) -> EnemyType {
+        let enemies = self.definition.enemies(night);
+        let ty = enemies[self.next_enemy % enemies.len()];
+        self.next_enemy += 1;
+
+        ty
+    }
+}
+
+#[derive(Component)]
+pub struct SpawnedBy(pub Entity);
+
+pub fn spawn_spawners(mut commands: Commands, spawners: Option<Res<Spawners>>) {
+    let Some(spawners) = spawners else {
+        return;
+    };
+
+    for definition in spawners.0.iter() {
+        if definition.enemies.is_empty() {
+            warn!("Skipping spawner without enemies at {:?}", definition.zone);
+            continue;
+        }
+
+        commands.spawn(Spawner::new(definition.clone()));
+    }
+}
+
+/// Where, what and when the spawners can spawn
+#[derive(SystemParam)]
+pub struct SpawnContext<'w, 's> {
+    window_q: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    camera_q: Query<'w, 's, (&'static Transform, &'static OrthographicProjection), With<Camera>>,
+    world_map: Res<'w, WorldMap>,
+    assets: Res<'w, GameAssets>,
+    atlases: Res<'w, Assets<TextureAtlas>>,
+    textures: Res<'w, Assets<TexturePack>>,
+    settings: Res<'w, Settings>,
+    cycle: Res<'w, DayCycle>,
+    time_of_day: Res<'w, TimeOfDay>,
+}
+
+pub fn update_spawners(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut spawner_q: Query<(Entity, &mut Spawner)>,
+    spawned_q: Query<&SpawnedBy>,
+    enemy_q: Query<(), With<Enemy>>,
+    context: SpawnContext,
+    mut rng: ResMut<GameRng>,
+) {
+    let Ok(window) = context.window_q.get_single() else {
+        return;
+    };
+
+    // Without a camera (e.g. when running headless) everything is off camera
+    let view = context.camera_q.get_single().ok().map(|(transform, projection)| {
+        Rect::from_center_size(
+            transform.translation.xy(),
+            Vec2::new(window.width(), window.height()) * projection.scale,
+        )
+    });
+
+    let mut num_enemies = enemy_q.iter().count();
+    let night = context.cycle.is_night(context.time_of_day.hour);
+
+    for (entity, mut spawner) in spawner_q.iter_mut() {
+        let alive = spawned_q.iter().filter(|spawned_by| spawned_by.0 == entity).count();
+
+        if alive >= spawner.definition.max_alive(night)
+            || !can_spawn(num_enemies + 1, context.settings.debug.max_enemies)
+        {
+            continue;
+        }
+
+        spawner.timer.tick(time.delta());
+
+        if !spawner.timer.finished() {
+            continue;
+        }
+
+        let definition = &spawner.definition;
+        let Some((x, y)) = find_spawn_position(
+            &context.world_map,
+            &definition.zone,
+            rng.stream(RngStream::Spawning),
+            |x, y| !definition.off_camera || !view.is_some_and(|view| view.contains(from_position(x, y, window).xy())),
+        ) else {
+            continue;
+        };
+
+        let ty = spawner.next_enemy(night);
+        let enemy = spawn_enemy(
+            &mut commands,
+            window,
+            &context.assets,
+            &context.atlases,
+            &context.textures,
+            ty,
+            x,
+            y,
+        );
+
+        commands.entity(enemy).insert(SpawnedBy(entity));
+        spawner.timer.reset();
+        num_enemies += 1;
+    }
+}
+
+fn find_spawn_position(
+    world_map: &WorldMap,
+    zone: &Zone,
+    rng: &mut impl Rng,
+    is_valid: impl Fn(f32, f32) -> bool,
+) -> Option<(f32, f32)> {
+    for _ in 0..MAX_SPAWN_ATTEMPTS {
+        let col = rng.gen_range(zone.x..zone.x + zone.width.max(1));
+        let row = rng.gen_range(zone.y..zone.y + zone.height.max(1));
+
+        if !world_map.is_free(row, col, &[LayerType::Blocks, LayerType::Objects]) {
+            continue;
+        }
+
+        let x = (col as f32 + 0.5) * TILE_SIZE;
+        let y = (row as f32 + 0.5) * TILE_SIZE;
+
+        if is_valid(x, y) {
+            return Some((x, y));
+        }
+    }
+
+    None
+}
@@ -28,6 +28,10 @@ impl Stat {
         self.value = val;
     }
 
+    pub fn set_limit(&mut self, val: u32) {
+        self.limit = val;
+    }
+
     pub fn limit(&self) -> u32 {
         self.limit
     }
@@ -0,0 +1,374 @@
+use std::{collections::HashMap, time::Duration};
+
+use bevy::{ecs::system::SystemParam, prelude::*, reflect::TypeUuid, window::PrimaryWindow};
+use bevy_rapier2d::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{
+    collisions::OBJECTS_COLLISION_GROUP,
+    constants::TILE_SIZE,
+    depth::YSort,
+    entities::{from_position, Attackable, Player, PlayerStat},
+    events::{DestructibleBroken, EmitParticleEffect, KillAttackable},
+    frames::TexturePack,
+    map::LayerType,
+    particles::ParticleEffect,
+    rng::{GameRng, RngStream},
+    settings::Settings,
+    GameAssetType,
+    GameAssets,
+    Layer,
+};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakParticle {
+    Leaf,
+    Smoke,
+    Sparkle,
+}
+
+impl From<BreakParticle> for ParticleEffect {
+    fn from(particle: BreakParticle) -> Self {
+        match particle {
+            BreakParticle::Leaf => ParticleEffect::Leaf,
+            BreakParticle::Smoke => ParticleEffect::Smoke,
+            BreakParticle::Sparkle => ParticleEffect::Sparkle,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DropItem {
+    Health,
+    Energy,
+    Xp,
+}
+
+impl DropItem {
+    pub fn texture_name(&self) -> &str {
+        match self {
+            DropItem::Health => "particles/heal/full.png",
+            DropItem::Energy => "particles/flame/full.png",
+            DropItem::Xp => "particles/sparkle/00.png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct DropDefinition {
+    pub item: DropItem,
+    pub amount: u32,
+    /// Probability, between 0 and 1, for the drop to happen
+    pub chance: f32,
+}
+
+/// Metadata shared by all the tiles of a layer with the given cell values
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileDefinition {
+    pub layer: LayerType,
+    pub cells: Vec<i32>,
+    pub health: u32,
+    pub particle: Option<BreakParticle>,
+    /// Seconds before a destroyed tile grows back
+    pub regrow_delay: Option<f32>,
+    #[serde(default)]
+    pub drops: Vec<DropDefinition>,
+}
+
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[serde(from = "Vec<TileDefinition>")]
+#[uuid = "6bbe1f79-1790-4a9b-9b04-77966300fdd9"]
+pub struct TileMetadata {
+    tiles: HashMap<(LayerType, i32), TileDefinition>,
+}
+
+impl TileMetadata {
+    pub fn get(&self, layer: LayerType, cell: i32) -> Option<&TileDefinition> {
+        self.tiles.get(&(layer, cell))
+    }
+}
+
+impl From<Vec<TileDefinition>> for TileMetadata {
+    fn from(definitions: Vec<TileDefinition>) -> Self {
+        let tiles = definitions
+            .into_iter()
+            .flat_map(|definition| {
+                definition
+                    .cells
+                    .clone()
+                    .into_iter()
+                    .map(move |cell| ((definition.layer, cell), definition.clone()))
+            })
+            .collect();
+
+        Self { tiles }
+    }
+}
+
+/// A tile that can be destroyed, with the map cell it was spawned from
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Destructible {
+    pub layer: LayerType,
+    pub cell: i32,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A destroyed tile waiting to grow back
+#[derive(Component)]
+pub struct Regrowth {
+    pub tile: Destructible,
+    pub timer: Timer,
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    item: DropItem,
+    amount: u32,
+}
+
+/// What it takes to spawn the tiles and the entities of the map
+#[derive(SystemParam)]
+pub struct LevelContext<'w> {
+    pub asset_server: Res<'w, AssetServer>,
+    pub assets: Res<'w, GameAssets>,
+    pub atlases: Res<'w, Assets<TextureAtlas>>,
+    pub textures: Res<'w, Assets<TexturePack>>,
+    pub metadata: Res<'w, TileMetadata>,
+    pub settings: Res<'w, Settings>,
+    pub rng: ResMut<'w, GameRng>,
+}
+
+pub fn spawn_tile(
+    commands: &mut Commands,
+    window: &Window,
+    context: &mut LevelContext,
+    layer_type: &LayerType,
+    cell: i32,
+    x: f32,
+    y: f32,
+) {
+    let definition = context.metadata.get(*layer_type, cell);
+    let index = layer_type.to_index(cell as usize, context.rng.stream(RngStream::Map));
+    let asset_type = layer_type.into();
+
+    let atlas_handle = context.assets.get(asset_type);
+    let atlas = context.atlases.get(atlas_handle).unwrap();
+    let rect = atlas.textures[index];
+    let offset = (rect.height() - TILE_SIZE) / 2.0;
+
+    let y = y - offset;
+
+    let collider_height = TILE_SIZE / 2.0;
+
+    let mut cmd = commands.spawn((
+        SpriteSheetBundle {
+            sprite: TextureAtlasSprite::new(index),
+            texture_atlas: atlas_handle.clone(),
+            transform: Transform::from_translation(from_position(x, y, window)),
+            ..Default::default()
+        },
+        RigidBody::Fixed,
+        Layer(*layer_type),
+        YSort::default(),
+    ));
+
+    cmd.with_children(|parent| {
+        let mut child = parent.spawn((
+            Collider::cuboid(rect.width() / 2.0, collider_height / 2.0),
+            Transform::from_xyz(0.0, -offset, 0.0),
+            ColliderDebugColor(Color::BLUE),
+        ));
+
+        if definition.is_some() {
+            // println!("Rect: {:?} {:?}", rect.width(), rect.height());
+            child.insert((
+                Collider::cuboid(rect.width() / 2.0, rect.height() / 2.0),
+                OBJECTS_COLLISION_GROUP.clone(),
+                ActiveEvents::COLLISION_EVENTS,
+                ColliderDebugColor(Color::BLACK),
+            ));
+        }
+    });
+
+    if let Some(definition) = definition {
+        cmd.insert((
+            Attackable::new(definition.health),
+            Destructible {
+                layer: *layer_type,
+                cell,
+                x,
+                y: y + offset,
+            },
+        ));
+    }
+}
+
+/// Breaks the killed tiles, for them to leave particles, drops and maybe grow back
+pub fn break_destructibles(
+    parent_q: Query<(&Destructible, &Transform)>,
+    metadata: Res<TileMetadata>,
+    mut kill_attackable_reader: EventReader<KillAttackable>,
+    mut particle_effect_writer: EventWriter<EmitParticleEffect>,
+    mut destructible_broken_writer: EventWriter<DestructibleBroken>,
+) {
+    for event in kill_attackable_reader.iter() {
+        let Ok((tile, transform)) = parent_q.get(event.0) else {
+            continue;
+        };
+
+        destructible_broken_writer.send(DestructibleBroken {
+            tile: *tile,
+            pos: transform.translation,
+        });
+
+        if let Some(particle) = metadata
+            .get(tile.layer, tile.cell)
+            .and_then(|definition| definition.particle)
+        {
+            particle_effect_writer.send(EmitParticleEffect::new(particle.into(), transform.translation));
+        }
+    }
+}
+
+pub fn schedule_regrowth(
+    mut commands: Commands,
+    metadata: Res<TileMetadata>,
+    mut destructible_broken_reader: EventReader<DestructibleBroken>,
+) {
+    for event in destructible_broken_reader.iter() {
+        let tile = event.tile;
+
+        let Some(delay) = metadata.get(tile.layer, tile.cell).and_then(|d| d.regrow_delay) else {
+            continue;
+        };
+
+        commands.spawn(Regrowth {
+            tile,
+            timer: Timer::new(Duration::from_secs_f32(delay), TimerMode::Once),
+        });
+    }
+}
+
+pub fn regrow_tiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<&Transform, With<Player>>,
+    mut regrowth_q: Query<(Entity, &mut Regrowth)>,
+    mut context: LevelContext,
+) {
+    let Ok(window) = window.get_single() else {
+        return;
+    };
+
+    let player_pos = player_q
+        .get_single()
+        .map(|transform| transform.translation.truncate())
+        .ok();
+
+    for (entity, mut regrowth) in regrowth_q.iter_mut() {
+        regrowth.timer.tick(time.delta());
+
+        if !regrowth.timer.finished() {
+            continue;
+        }
+
+        let tile = regrowth.tile;
+
+        // Wait for the player to step away before growing back
+        let pos = from_position(tile.x, tile.y, window).truncate();
+        if player_pos.is_some_and(|player_pos| player_pos.distance(pos) < TILE_SIZE) {
+            continue;
+        }
+
+        spawn_tile(
+            &mut commands,
+            window,
+            &mut context,
+            &tile.layer,
+            tile.cell,
+            tile.x,
+            tile.y,
+        );
+
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn spawn_drops(
+    mut commands: Commands,
+    metadata: Res<TileMetadata>,
+    assets: Res<GameAssets>,
+    textures: Res<Assets<TexturePack>>,
+    mut destructible_broken_reader: EventReader<DestructibleBroken>,
+    mut rng: ResMut<GameRng>,
+) {
+    let pack = assets.pack(GameAssetType::Particles, &textures);
+    let rng = rng.stream(RngStream::Loot);
+
+    for event in destructible_broken_reader.iter() {
+        let Some(definition) = metadata.get(event.tile.layer, event.tile.cell) else {
+            continue;
+        };
+
+        for drop in definition.drops.iter() {
+            if rng.gen::<f32>() >= drop.chance {
+                continue;
+            }
+
+            let offset = Vec3::new(
+                rng.gen_range(-TILE_SIZE / 4.0..TILE_SIZE / 4.0),
+                rng.gen_range(-TILE_SIZE / 4.0..TILE_SIZE / 4.0),
+                1.0,
+            );
+
+            commands.spawn((
+                SpriteSheetBundle {
+                    sprite: TextureAtlasSprite::new(pack.index_of(drop.item.texture_name())),
+                    texture_atlas: assets.get(GameAssetType::Particles).clone(),
+                    transform: Transform::from_translation(event.pos + offset).with_scale(Vec3::splat(0.5)),
+                    ..default()
+                },
+                Pickup {
+                    item: drop.item,
+                    amount: drop.amount,
+                },
+            ));
+        }
+    }
+}
+
+pub fn collect_pickups(
+    mut commands: Commands,
+    mut player_q: Query<(&mut Player, &Transform)>,
+    pickup_q: Query<(Entity, &Pickup, &Transform)>,
+    mut particle_effect_writer: EventWriter<EmitParticleEffect>,
+) {
+    let Ok((mut player, player_transform)) = player_q.get_single_mut() else {
+        return;
+    };
+
+    for (entity, pickup, transform) in pickup_q.iter() {
+        let distance = player_transform
+            .translation
+            .truncate()
+            .distance(transform.translation.truncate());
+
+        if distance > TILE_SIZE / 2.0 {
+            continue;
+        }
+
+        match pickup.item {
+            DropItem::Health => player.gain(PlayerStat::Health, pickup.amount),
+            DropItem::Energy => player.gain(PlayerStat::Energy, pickup.amount),
+            DropItem::Xp => player.add_xp(pickup.amount),
+        }
+
+        particle_effect_writer.send(EmitParticleEffect::new(ParticleEffect::Sparkle, transform.translation));
+        commands.entity(entity).despawn();
+    }
+}
@@ -7,6 +7,8 @@ use crate::{
         BAR_HEIGHT,
         BORDER_COLOR,
         BORDER_WIDTH,
+        BOSS_BAR_WIDTH,
+        BOSS_HEALTH_COLOR,
         ENERGY_BAR_WIDTH,
         ENERGY_COLOR,
         FONT_SIZE,
@@ -17,7 +19,8 @@ use crate::{
         PADDING,
         SWITCH_ITEM_DURATION,
     },
-    entities::{Player, PlayerStat},
+    daytime::TimeOfDay,
+    entities::{Attackable, Boss, Enemy, Player, PlayerStat},
     frames::TexturePack,
     magic::Magic,
     weapon::Weapon,
@@ -35,12 +38,21 @@ pub struct SwitchWeaponTimer(pub Timer);
 #[derive(Component)]
 pub struct HealthBar;
 
+#[derive(Component)]
+pub struct BossHealthBar;
+
+#[derive(Component)]
+pub struct BossHealth;
+
 #[derive(Component)]
 pub struct Experience;
 
 #[derive(Component)]
 pub struct EnergyBar;
 
+#[derive(Component)]
+pub struct Clock;
+
 #[derive(Clone, Copy, Display)]
 pub enum ItemBoxType {
     #[display("{0}")]
@@ -57,10 +69,10 @@ impl ItemBoxType {
         }
     }
 
-    pub fn asset_name(&self) -> &str {
+    pub fn asset_type(&self) -> GameAssetType {
         match self {
-            ItemBoxType::Magic(_, _) => "particles",
-            ItemBoxType::Weapon(_, _) => "weapons",
+            ItemBoxType::Magic(_, _) => GameAssetType::Particles,
+            ItemBoxType::Weapon(_, _) => GameAssetType::Weapons,
         }
     }
 }
@@ -110,6 +122,37 @@ pub fn spawn_ui(
                 UiRect::top(Val::Px(2. * PADDING)),
                 EnergyBar,
             );
+
+            spawn_clock(parent, &asset_server);
+        });
+
+    // Boss content
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.), Val::Percent(100.)),
+                    padding: UiRect::all(Val::Px(MARGIN)),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexStart,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            BossHealth,
+        ))
+        .with_children(|parent| {
+            spawn_bar(
+                parent,
+                BOSS_BAR_WIDTH,
+                BOSS_BAR_WIDTH,
+                BOSS_HEALTH_COLOR,
+                UiRect::top(Val::Px(0.0)),
+                BossHealthBar,
+            );
         });
 
     // Bottom content
@@ -151,7 +194,6 @@ pub fn spawn_ui(
                             spawn_item_box(
                                 ItemBoxType::Weapon(*weapon, WeaponItemBox),
                                 parent,
-                                &asset_server,
                                 &assets,
                                 &textures,
                                 UiRect::all(Val::Px(0.)),
@@ -159,7 +201,6 @@ pub fn spawn_ui(
                             spawn_item_box(
                                 ItemBoxType::Magic(*magic, MagicItemBox),
                                 parent,
-                                &asset_server,
                                 &assets,
                                 &textures,
                                 UiRect::all(Val::Px(-4.0 * PADDING)),
@@ -207,17 +248,12 @@ fn spawn_bar<B: Bundle>(
 fn spawn_item_box(
     ty: ItemBoxType,
     parent: &mut ChildBuilder,
-    asset_server: &Res<AssetServer>,
     assets: &Res<GameAssets>,
     textures: &Res<Assets<TexturePack>>,
     margin: UiRect,
 ) {
     let name = format!("{}/full.png", ty.name());
-    let asset_name = format!("textures/{}.json", ty.asset_name());
-    // println!("{name} from {asset_name}");
-
-    let handle = asset_server.load(asset_name);
-    let pack = textures.get(&handle).expect("Texture pack must exist");
+    let pack = assets.pack(ty.asset_type(), textures);
     let index = pack.index_of(&name);
 
     let mut commands = parent.spawn((NodeBundle {
@@ -299,12 +335,40 @@ fn spawn_experience(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>)
         });
 }
 
+fn spawn_clock(parent: &mut ChildBuilder, asset_server: &Res<AssetServer>) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                size: Size::AUTO,
+                align_self: AlignSelf::FlexStart,
+                margin: UiRect::top(Val::Px(4. * PADDING)),
+                padding: UiRect::new(Val::Px(MARGIN), Val::Px(MARGIN), Val::Px(PADDING), Val::Px(PADDING)),
+                ..default()
+            },
+            background_color: BACK_COLOR.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections([TextSection::new(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/joystix.ttf"),
+                        font_size: FONT_SIZE,
+                        color: Color::WHITE,
+                    },
+                )]),
+                Clock,
+            ));
+        });
+}
+
 pub fn change_magic_item(
     mut commands: Commands,
     mut box_q: Query<&mut BackgroundColor, With<MagicItemBox>>,
     mut magic_q: Query<(&mut UiAtlasImage, &mut Magic)>,
     current_magic: Res<Magic>,
-    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
     textures: Res<Assets<TexturePack>>,
 ) {
     if current_magic.is_changed() {
@@ -321,8 +385,7 @@ pub fn change_magic_item(
             *magic = current_magic;
 
             let name = format!("particles/{current_magic}/full.png");
-            let handle = asset_server.load("textures/particles.json");
-            let pack = textures.get(&handle).expect("Texture pack must exist");
+            let pack = assets.pack(GameAssetType::Particles, &textures);
 
             image.index = pack.index_of(&name);
         }
@@ -352,7 +415,7 @@ pub fn change_weapon_item(
     mut box_q: Query<&mut BackgroundColor, With<WeaponItemBox>>,
     mut weapon_q: Query<(&mut UiAtlasImage, &mut Weapon)>,
     current_weapon: Res<Weapon>,
-    asset_server: Res<AssetServer>,
+    assets: Res<GameAssets>,
     textures: Res<Assets<TexturePack>>,
 ) {
     if current_weapon.is_changed() {
@@ -369,8 +432,7 @@ pub fn change_weapon_item(
             *weapon = current_weapon;
 
             let name = format!("weapons/{current_weapon}/full.png");
-            let handle = asset_server.load("textures/weapons.json");
-            let pack = textures.get(&handle).expect("Texture pack must exist");
+            let pack = assets.pack(GameAssetType::Weapons, &textures);
 
             image.index = pack.index_of(&name);
         }
@@ -418,3 +480,32 @@ pub fn update_xp_ui(player_q: Query<&Player>, mut xp_q: Query<&mut Text, With<Ex
 
     text.sections[0].value = format!("{}", player.xp);
 }
+
+pub fn update_clock_ui(time_of_day: Res<TimeOfDay>, mut clock_q: Query<&mut Text, With<Clock>>) {
+    let mut text = clock_q.single_mut();
+
+    let clock = time_of_day.clock();
+    if text.sections[0].value != clock {
+        text.sections[0].value = clock;
+    }
+}
+
+pub fn update_boss_health_ui(
+    boss_q: Query<(&Boss, &Enemy, &Attackable)>,
+    mut container_q: Query<&mut Visibility, With<BossHealth>>,
+    mut health_q: Query<&mut Style, With<BossHealthBar>>,
+) {
+    let mut visibility = container_q.single_mut();
+
+    let Some((_, enemy, attackable)) = boss_q.iter().find(|(boss, _, _)| boss.active) else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    *visibility = Visibility::Inherited;
+
+    let mut health = health_q.single_mut();
+    let ratio = attackable.health() as f32 / enemy.health() as f32;
+
+    health.size.width = Val::Px(ratio * BOSS_BAR_WIDTH);
+}
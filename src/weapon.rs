@@ -1,13 +1,14 @@
 use std::time::Duration;
 
-use bevy_kira_audio::{Audio, AudioControl};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use parse_display::Display;
 
 use crate::{
+    audio::PlaySound,
     collisions::WEAPON_COLLISION_GROUP,
     constants::TILE_SIZE,
+    depth::YSort,
     entities::{Direction, Player},
     events::SwitchWeapon,
     frames::TexturePack,
@@ -78,10 +79,9 @@ pub fn spawn_weapon(
     current_weapon: Res<Weapon>,
     player_q: Query<(Entity, &Player)>,
     weapon_q: Query<Entity, With<PlayerWeapon>>,
-    asset_server: Res<AssetServer>,
     assets: Res<GameAssets>,
     textures: Res<Assets<TexturePack>>,
-    audio: Res<Audio>,
+    mut play_sound_writer: EventWriter<PlaySound>,
 ) {
     let Err(_) = weapon_q.get_single() else {
         return;
@@ -97,10 +97,9 @@ pub fn spawn_weapon(
     let weapon = *current_weapon;
 
     let name = format!("weapons/{weapon}/{direction}.png");
-    let handle = asset_server.load("textures/weapons.json");
-    let pack = textures.get(&handle).expect("Texture pack must exist");
+    let pack = assets.pack(GameAssetType::Weapons, &textures);
     let index = pack.index_of(&name);
-    let frame = &pack.frames[&name];
+    let frame = pack.frame(&name);
 
     let y_offset = TILE_SIZE - (TILE_SIZE - frame.frame.h) / 2.0 - 4.0;
     let x_offset = (TILE_SIZE + frame.frame.w) / 2.0;
@@ -122,6 +121,7 @@ pub fn spawn_weapon(
             },
             *current_weapon,
             PlayerWeapon,
+            YSort::default(),
             ActiveEvents::COLLISION_EVENTS,
             Collider::cuboid(frame.frame.w / 2.0, frame.frame.h / 2.0),
             WEAPON_COLLISION_GROUP.clone(),
@@ -129,7 +129,7 @@ pub fn spawn_weapon(
         ));
     });
 
-    audio.play(asset_server.load("audio/sword.wav")).with_volume(0.4);
+    play_sound_writer.send(PlaySound::sfx("audio/sword.wav").with_volume(0.4));
 }
 
 pub fn switch_weapon(mut current_weapon: ResMut<Weapon>, mut reader: EventReader<SwitchWeapon>) {
@@ -0,0 +1,239 @@
+use bevy::{prelude::*, reflect::TypeUuid, sprite::Anchor, window::PrimaryWindow};
+use serde::Deserialize;
+
+use crate::{
+    constants::{
+        FOG_COLOR,
+        FOG_DEPTH,
+        FOG_NOTICE_FACTOR,
+        RAIN_EXTINGUISH_THRESHOLD,
+        RAIN_NOTICE_FACTOR,
+        WEATHER_TRANSITION_SPEED,
+        WIND_SWAY,
+        WIND_SWAY_SPEED,
+    },
+    daytime::DayTime,
+    depth::above_map,
+    entities::{to_cell, to_position, Player},
+    events::EmitParticleEffect,
+    magic::Magic,
+    map::{LayerType, Zone},
+    particles::{ParticleEffect, ParticleEffectAnimation, ParticleEmitter, Rain},
+    Layer,
+    MapSize,
+};
+
+/// How strong the rain, the fog and the wind are, from 0 to 1
+#[derive(Debug, Clone, Copy, Default, PartialEq, Resource, Deserialize)]
+#[serde(default)]
+pub struct Weather {
+    pub rain: f32,
+    pub fog: f32,
+    pub wind: f32,
+}
+
+impl Weather {
+    /// Goes towards the given weather, by at most `step` for each of them
+    fn blend(&mut self, target: &Weather, step: f32) {
+        let approach = |from: f32, to: f32| from + (to - from).clamp(-step, step);
+
+        self.rain = approach(self.rain, target.rain);
+        self.fog = approach(self.fog, target.fog);
+        self.wind = approach(self.wind, target.wind);
+    }
+
+    /// How far the enemies notice the player, compared to a clear day
+    pub fn notice_factor(&self) -> f32 {
+        (1. - self.rain * RAIN_NOTICE_FACTOR - self.fog * FOG_NOTICE_FACTOR).max(0.)
+    }
+
+    pub fn extinguishes_flames(&self) -> bool {
+        self.rain >= RAIN_EXTINGUISH_THRESHOLD
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeatherRegion {
+    pub zone: Zone,
+    #[serde(flatten)]
+    pub weather: Weather,
+}
+
+/// The weather in some areas of the map, clear everywhere else
+#[derive(Debug, Clone, Default, Resource, Deserialize, TypeUuid)]
+#[uuid = "702300c3-230a-4ddd-8493-1e81d65ea66c"]
+pub struct WeatherRegions(pub Vec<WeatherRegion>);
+
+impl WeatherRegions {
+    /// The weather of the first region containing the cell
+    pub fn at(&self, row: usize, col: usize) -> Weather {
+        self.0
+            .iter()
+            .find(|region| region.zone.contains(row, col))
+            .map(|region| region.weather)
+            .unwrap_or_default()
+    }
+}
+
+/// Covers the view with fog
+#[derive(Component)]
+pub struct FogOverlay;
+
+/// The weather changes gradually, as the player walks in and out of the regions
+pub fn update_weather(
+    time: Res<Time>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    player_q: Query<&Transform, With<Player>>,
+    regions: Res<WeatherRegions>,
+    mut weather: ResMut<Weather>,
+) {
+    let (Ok(window), Ok(transform)) = (window_q.get_single(), player_q.get_single()) else {
+        return;
+    };
+
+    let target = to_cell(to_position(transform.translation, window))
+        .map(|(row, col)| regions.at(row, col))
+        .unwrap_or_default();
+
+    if *weather != target {
+        weather.blend(&target, WEATHER_TRANSITION_SPEED * time.delta_seconds());
+    }
+}
+
+pub fn extinguish_flames(
+    mut commands: Commands,
+    flame_q: Query<(Entity, &Magic, &Transform), With<ParticleEffectAnimation>>,
+    weather: Res<Weather>,
+    mut particle_effect_writer: EventWriter<EmitParticleEffect>,
+) {
+    if !weather.extinguishes_flames() {
+        return;
+    }
+
+    for (entity, magic, transform) in flame_q.iter() {
+        if matches!(magic, Magic::Flame) {
+            commands.entity(entity).despawn_recursive();
+            particle_effect_writer.send(EmitParticleEffect::new(ParticleEffect::Smoke, transform.translation));
+        }
+    }
+}
+
+pub fn update_rain(mut emitter_q: Query<&mut ParticleEmitter, With<Rain>>, weather: Res<Weather>) {
+    for mut emitter in emitter_q.iter_mut() {
+        let active = weather.rain > 0.;
+
+        if emitter.active != active || emitter.intensity != weather.rain {
+            emitter.active = active;
+            emitter.intensity = weather.rain;
+        }
+    }
+}
+
+pub fn spawn_fog(mut commands: Commands, size: Res<MapSize>) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: Color::NONE,
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., above_map(&size) + FOG_DEPTH),
+            ..default()
+        },
+        FogOverlay,
+    ));
+}
+
+type FogCameraQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static OrthographicProjection), (With<Camera>, Without<FogOverlay>)>;
+type FogQuery<'w, 's> =
+    Query<'w, 's, (&'static mut Transform, &'static mut Sprite, &'static mut Visibility), With<FogOverlay>>;
+
+/// Stretches the fog over the view, as thick as the weather wants
+pub fn update_fog(
+    camera_q: FogCameraQuery,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    mut fog_q: FogQuery,
+    weather: Res<Weather>,
+    day_time: DayTime,
+) {
+    let (Ok((camera, projection)), Ok(window)) = (camera_q.get_single(), window_q.get_single()) else {
+        return;
+    };
+
+    // Darker at night, like the rest of the world
+    let tint = day_time.tint();
+
+    for (mut transform, mut sprite, mut visibility) in fog_q.iter_mut() {
+        *visibility = if weather.fog > 0. {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+
+        transform.translation.x = camera.translation.x;
+        transform.translation.y = camera.translation.y;
+        sprite.custom_size = Some(Vec2::new(window.width(), window.height()) * projection.scale);
+        sprite.color = Color::rgba(
+            FOG_COLOR.r() * tint.r(),
+            FOG_COLOR.g() * tint.g(),
+            FOG_COLOR.b() * tint.b(),
+            FOG_COLOR.a() * weather.fog,
+        );
+    }
+}
+
+/// The grass sways in the wind, a wave going through it from left to right
+pub fn sway_grass(
+    time: Res<Time>,
+    mut grass_q: Query<(&mut TextureAtlasSprite, &Transform, &Layer)>,
+    weather: Res<Weather>,
+) {
+    let elapsed = time.elapsed_seconds();
+
+    for (mut sprite, transform, layer) in grass_q.iter_mut() {
+        if layer.0 != LayerType::Grass {
+            continue;
+        }
+
+        let anchor = if weather.wind > 0. {
+            let phase = elapsed * WIND_SWAY_SPEED - transform.translation.x / 100.;
+            // The anchor goes the other way than the sprite, in fractions of its size
+            Anchor::Custom(Vec2::new(-phase.sin() * weather.wind * WIND_SWAY, 0.))
+        } else {
+            Anchor::Center
+        };
+
+        // Only drawn differently, so the sprites are not marked as changed (e.g. to sort them again) at every frame
+        if sprite.anchor.as_vec() != anchor.as_vec() {
+            sprite.bypass_change_detection().anchor = anchor;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weather_by_region() {
+        let regions: WeatherRegions = serde_json::from_str(
+            r#"[
+                { "zone": { "x": 0, "y": 0, "width": 10, "height": 10 }, "rain": 1.0 },
+                { "zone": { "x": 5, "y": 5, "width": 10, "height": 10 }, "fog": 0.5 }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(regions.at(6, 6).rain, 1.);
+        assert_eq!(regions.at(12, 12).fog, 0.5);
+        assert_eq!(regions.at(20, 20), Weather::default());
+
+        let mut weather = Weather::default();
+        weather.blend(&regions.at(0, 0), 0.6);
+        assert_eq!(weather.rain, 0.6);
+        weather.blend(&regions.at(0, 0), 0.6);
+        assert_eq!(weather.rain, 1.);
+        assert!(weather.extinguishes_flames());
+        assert!(weather.notice_factor() < 1.);
+    }
+}